@@ -0,0 +1,55 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use sourcelink_cli::{
+    commands::{scan_files, ScanOptions},
+    config::Config,
+    lang::Lang,
+    links::find_links,
+    walk::collect_files,
+};
+use std::{fs, path::Path};
+use tempfile::TempDir;
+
+/// Copies of each fixture to scan, enough for parallelism to pay off.
+const COPIES: usize = 200;
+
+fn fixtures() -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let test_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test");
+    for i in 0..COPIES {
+        let copy = dir.path().join(i.to_string());
+        fs::create_dir(&copy).unwrap();
+        for entry in fs::read_dir(&test_dir).unwrap() {
+            let path = entry.unwrap().path();
+            fs::copy(&path, copy.join(path.file_name().unwrap())).unwrap();
+        }
+    }
+    dir
+}
+
+fn scan(c: &mut Criterion) {
+    let dir = fixtures();
+    let paths = [dir.path().to_owned()];
+    let config = Config::default();
+    let mut group = c.benchmark_group("scan");
+    group.sample_size(20);
+    group.bench_function("Lang::parse", |b| {
+        b.iter(|| {
+            let mut links = 0;
+            for path in collect_files(&paths, &config).unwrap() {
+                let content = fs::read_to_string(&path).unwrap();
+                let lang = Lang::from_filename(&path.to_string_lossy()).unwrap();
+                for comment in lang.parse(&content).unwrap() {
+                    links += find_links(&content, &comment, None).len();
+                }
+            }
+            links
+        })
+    });
+    group.bench_function("scan_files", |b| {
+        b.iter(|| scan_files(&config, &paths, &ScanOptions::default(), None).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, scan);
+criterion_main!(benches);
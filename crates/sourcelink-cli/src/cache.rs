@@ -0,0 +1,165 @@
+use crate::commands::{FileReport, FoundLink};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Bump whenever a change to detection, parsing or link extraction could
+/// find different links in an unchanged file.
+pub const PARSER_VERSION: u32 = 8;
+
+const CACHE_DIR: &str = "sourcelink";
+const CACHE_FILE: &str = "cache.json";
+
+/// What was found in a file with a given content hash.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CacheEntry {
+    pub hash: String,
+    pub lang: String,
+    pub links: Vec<FoundLink>,
+}
+
+impl CacheEntry {
+    pub fn report(&self, filename: &str) -> FileReport {
+        FileReport {
+            filename: filename.to_owned(),
+            lang: self.lang.clone(),
+            links: self.links.clone(),
+            ..FileReport::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct CacheData {
+    fingerprint: String,
+    /// Entries by path relative to the repository root.
+    files: BTreeMap<String, CacheEntry>,
+}
+
+/// Scan results kept under `.git/sourcelink/`, so unchanged files don't need
+/// to be parsed again.
+pub struct Cache {
+    path: PathBuf,
+    root: PathBuf,
+    data: CacheData,
+}
+
+pub fn hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// The git directory and work tree root containing `dir`, if any.
+pub fn find_git_dir(dir: &Path) -> Option<(PathBuf, PathBuf)> {
+    dir.ancestors().find_map(|root| {
+        let dot_git = root.join(".git");
+        if dot_git.is_dir() {
+            return Some((dot_git, root.to_owned()));
+        }
+        // Worktrees and submodules have a file pointing at the git directory.
+        let content = fs::read_to_string(&dot_git).ok()?;
+        let git_dir = content.strip_prefix("gitdir:")?.trim();
+        Some((root.join(git_dir), root.to_owned()))
+    })
+}
+
+impl Cache {
+    /// Open the cache of the repository containing the current directory.
+    /// `fingerprint` identifies everything besides file contents that the
+    /// results depend on; a cache with a different one is discarded.
+    pub fn discover(fingerprint: &str) -> Result<Option<Self>> {
+        let cwd = env::current_dir()?;
+        Ok(find_git_dir(&cwd)
+            .map(|(git_dir, root)| Self::open(&git_dir.join(CACHE_DIR), &root, fingerprint)))
+    }
+
+    pub fn open(dir: &Path, root: &Path, fingerprint: &str) -> Self {
+        let path = dir.join(CACHE_FILE);
+        let data = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<CacheData>(&bytes).ok())
+            .filter(|data| data.fingerprint == fingerprint)
+            .unwrap_or_else(|| CacheData {
+                fingerprint: fingerprint.to_owned(),
+                files: BTreeMap::new(),
+            });
+        Self {
+            path,
+            root: root.canonicalize().unwrap_or_else(|_| root.to_owned()),
+            data,
+        }
+    }
+
+    /// Key for a file, or `None` if it is outside the repository.
+    pub fn key(&self, path: &Path) -> Option<String> {
+        let path = path.canonicalize().ok()?;
+        Some(
+            path.strip_prefix(&self.root)
+                .ok()?
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+
+    pub fn get(&self, key: &str, hash: &str) -> Option<&CacheEntry> {
+        self.data.files.get(key).filter(|entry| entry.hash == hash)
+    }
+
+    pub fn insert(&mut self, key: String, entry: CacheEntry) {
+        self.data.files.insert(key, entry);
+    }
+
+    /// Write the cache back, dropping entries for files that no longer exist.
+    pub fn save(&mut self) -> Result<()> {
+        let root = &self.root;
+        self.data.files.retain(|key, _| root.join(key).is_file());
+        let dir = self.path.parent().unwrap();
+        fs::create_dir_all(dir)?;
+        // Write to a temporary file first so an interrupted run can't leave a
+        // truncated cache behind.
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(&self.data)?)?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Unable to write cache {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let repo = tempfile::tempdir().unwrap();
+        let dir = repo.path().join(".git").join(CACHE_DIR);
+        let file = repo.path().join("main.c");
+        fs::write(&file, "// https://example.com\n").unwrap();
+        let entry = CacheEntry {
+            hash: hash(b"// https://example.com\n"),
+            lang: "c".to_owned(),
+            links: vec![FoundLink {
+                line: 1,
+                column: 4,
+                url: "https://example.com".to_owned(),
+            }],
+        };
+
+        let mut cache = Cache::open(&dir, repo.path(), "v1");
+        let key = cache.key(&file).unwrap();
+        assert_eq!(key, "main.c");
+        cache.insert(key.clone(), entry.clone());
+        cache.insert("deleted.c".to_owned(), entry.clone());
+        cache.save().unwrap();
+
+        let cache = Cache::open(&dir, repo.path(), "v1");
+        assert!(cache.get(&key, &entry.hash).is_some());
+        assert!(cache.get(&key, &hash(b"changed")).is_none());
+        assert!(cache.get("deleted.c", &entry.hash).is_none());
+        let cache = Cache::open(&dir, repo.path(), "v2");
+        assert!(cache.get(&key, &entry.hash).is_none());
+    }
+}
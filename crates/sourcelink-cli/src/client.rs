@@ -0,0 +1,116 @@
+use crate::{
+    config::{Config, ServerConfig},
+    error::SourcelinkError,
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::env;
+
+/// Environment variable the API key is read from, so it can be kept out of
+/// `.sourcelink.toml`.
+pub const API_KEY_VAR: &str = "SOURCELINK_API_KEY";
+
+/// A link as returned by the server.
+#[derive(Deserialize, Debug)]
+pub struct LinkRow {
+    pub id: String,
+    pub url: String,
+}
+
+#[derive(Serialize)]
+struct CreateLink<'a> {
+    project_id: &'a str,
+    url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<&'a str>,
+}
+
+/// Client for the link shortening service.
+pub struct Client<'config> {
+    server: &'config ServerConfig,
+    api_key: String,
+    agent: ureq::Agent,
+}
+
+impl<'config> Client<'config> {
+    pub fn new(config: &'config Config) -> Result<Self> {
+        let server = config.server.as_ref().ok_or(SourcelinkError::NoServer)?;
+        let api_key = env::var(API_KEY_VAR)
+            .ok()
+            .or_else(|| server.api_key.clone())
+            .ok_or(SourcelinkError::NoApiKey)?;
+        Ok(Self {
+            server,
+            api_key,
+            agent: ureq::Agent::new(),
+        })
+    }
+
+    fn endpoint(&self, path: &str) -> String {
+        format!("{}/api/{path}", self.server.url.trim_end_matches('/'))
+    }
+
+    /// Create a link to `url`, with the id `id` if given or a random one
+    /// otherwise.
+    pub fn create_link(&self, url: &str, id: Option<&str>) -> Result<LinkRow> {
+        let response = self
+            .agent
+            .post(&self.endpoint("links"))
+            .set("X-API-Key", &self.api_key)
+            .send_json(CreateLink {
+                project_id: &self.server.project,
+                url,
+                id,
+            })
+            .with_context(|| format!("Unable to shorten {url}"))?;
+        Ok(response.into_json()?)
+    }
+
+    pub fn get_link(&self, id: &str) -> Result<LinkRow> {
+        let response = self
+            .agent
+            .get(&self.endpoint(&format!("link/{id}")))
+            .set("X-API-Key", &self.api_key)
+            .call()
+            .with_context(|| format!("Unable to look up link {id}"))?;
+        Ok(response.into_json()?)
+    }
+
+    pub fn delete_link(&self, id: &str) -> Result<()> {
+        self.agent
+            .delete(&self.endpoint(&format!("link/{id}")))
+            .set("X-API-Key", &self.api_key)
+            .call()
+            .with_context(|| format!("Unable to delete link {id}"))?;
+        Ok(())
+    }
+
+    pub fn short_url(&self, id: &str) -> String {
+        self.server.short_url(id)
+    }
+
+    pub fn link_id<'url>(&self, url: &'url str) -> Option<&'url str> {
+        self.server.link_id(url)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn short_links() {
+        let config = Config::parse(
+            "[server]\nurl = \"https://sl.example.com/\"\nproject = \"p\"\napi_key = \"k\"",
+        )
+        .unwrap();
+        let client = Client::new(&config).unwrap();
+        assert_eq!(client.short_url("V1StGX"), "https://sl.example.com/V1StGX");
+        assert_eq!(
+            client.link_id("https://sl.example.com/V1StGX"),
+            Some("V1StGX")
+        );
+        assert_eq!(client.link_id("https://sl.example.com/api/links"), None);
+        assert_eq!(client.link_id("https://example.com/V1StGX"), None);
+    }
+}
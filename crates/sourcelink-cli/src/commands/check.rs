@@ -0,0 +1,31 @@
+use super::scan::{scan_reports, ScanOptions};
+use crate::{config::Config, error::SourcelinkError};
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// Print the links `shorten` would replace, failing if there are any.
+pub fn check(config: &Config, paths: &[PathBuf], options: &ScanOptions) -> Result<()> {
+    if config.server.is_none() {
+        return Err(SourcelinkError::NoServer.into());
+    }
+    let (reports, failed) = scan_reports(config, paths, options)?;
+    let mut unshortened = 0;
+    for report in &reports {
+        for link in &report.links {
+            if config.verdict(&link.url).shorten() {
+                println!(
+                    "{}:{}:{}: {}",
+                    report.filename, link.line, link.column, link.url
+                );
+                unshortened += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        Err(SourcelinkError::ParseFailed(failed).into())
+    } else if unshortened > 0 {
+        Err(SourcelinkError::Unshortened(unshortened).into())
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use super::rewrite::{echo_on_error, rewrite_links, Candidate, RewriteOptions};
+use crate::{client::Client, config::Config};
+use anyhow::Result;
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+/// Replace short links in the comments of the files under `paths` with the
+/// URLs they point to. A path of `-` filters `input` to `output`.
+pub fn expand(
+    config: &Config,
+    paths: &[PathBuf],
+    options: &RewriteOptions,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+) -> Result<()> {
+    let client = echo_on_error(paths, options, input, output, || Client::new(config))?;
+    let mut expanded: HashMap<String, String> = HashMap::new();
+    let journal = options.journal("expand", paths)?;
+    rewrite_links(
+        config,
+        paths,
+        options,
+        journal.as_ref(),
+        input,
+        output,
+        |candidate: &Candidate| {
+            let Some(id) = client.link_id(candidate.url) else {
+                return Ok(None);
+            };
+            if let Some(long) = expanded.get(id) {
+                return Ok(Some(long.clone()));
+            }
+            let long = client.get_link(id)?.url;
+            expanded.insert(id.to_owned(), long.clone());
+            Ok(Some(long))
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stdin_without_server() {
+        let input = "# https://sl.example/abc\n";
+        let options = RewriteOptions {
+            lang: Some("python".to_owned()),
+            ..Default::default()
+        };
+        let mut output = vec![];
+        let result = expand(
+            &Config::default(),
+            &[PathBuf::from("-")],
+            &options,
+            &mut input.as_bytes(),
+            &mut output,
+        );
+        assert!(result.is_err());
+        assert_eq!(output, input.as_bytes());
+    }
+}
@@ -0,0 +1,20 @@
+use crate::config::Config;
+use anyhow::Result;
+
+/// Print whether `shorten` would replace each of `urls`, and why, along with
+/// the URL it would link to if normalizing changes it.
+pub fn explain(config: &Config, urls: &[String]) -> Result<()> {
+    for url in urls {
+        let verdict = config.verdict(url);
+        println!(
+            "{url}: {}, {}",
+            verdict.action,
+            config.shorten.describe(&verdict)
+        );
+        let normalized = config.shorten.normalize.apply(url);
+        if verdict.shorten() && normalized != *url {
+            println!("  normalized to {normalized}");
+        }
+    }
+    Ok(())
+}
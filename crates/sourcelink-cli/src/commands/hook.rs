@@ -0,0 +1,149 @@
+use crate::git;
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use std::{fs, io, path::Path};
+
+const BEGIN: &str = "# >>> sourcelink >>>";
+const END: &str = "# <<< sourcelink <<<";
+const HOOK: &str = "pre-commit";
+
+#[derive(Subcommand, Debug)]
+pub enum HookCommand {
+    /// Add a pre-commit hook, or a section to an existing one
+    Install {
+        /// Shorten links in staged changes instead of failing the commit
+        #[arg(long)]
+        shorten: bool,
+    },
+    /// Remove the section added by `install`
+    Uninstall,
+}
+
+/// The hook with our section added, replacing any section already there.
+fn with_section(existing: Option<&str>, command: &str) -> String {
+    let section = format!("{BEGIN}\n{command} || exit $?\n{END}\n");
+    let base = existing.map(|existing| without_section(existing).unwrap_or(existing.to_owned()));
+    match base {
+        Some(base) if !base.trim().is_empty() => {
+            let separator = if base.ends_with('\n') { "" } else { "\n" };
+            format!("{base}{separator}{section}")
+        }
+        _ => format!("#!/bin/sh\n{section}"),
+    }
+}
+
+/// The hook with our section removed, or `None` if it has none.
+fn without_section(content: &str) -> Option<String> {
+    let mut lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let begin = lines.iter().position(|line| line.trim_end() == BEGIN)?;
+    let end = begin
+        + lines[begin..]
+            .iter()
+            .position(|line| line.trim_end() == END)?;
+    lines.drain(begin..=end);
+    Some(lines.concat())
+}
+
+/// Whether a hook stops before reaching a section appended to it.
+fn ends_early(content: &str) -> bool {
+    content
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty() && !line.starts_with('#'))
+        .is_some_and(|line| line.starts_with("exit") || line.starts_with("exec "))
+}
+
+fn install(path: &Path, shorten: bool) -> Result<()> {
+    let existing = match fs::read_to_string(path) {
+        Ok(existing) => Some(existing),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err.into()),
+    };
+    if let Some(existing) = &existing {
+        if ends_early(&without_section(existing).unwrap_or(existing.clone())) {
+            log::warn!(
+                "{} exits before the end, so the added section may never run",
+                path.display()
+            );
+        }
+    }
+    // `shorten --staged` stages what it rewrites itself.
+    let command = if shorten {
+        "sourcelink shorten --staged"
+    } else {
+        "sourcelink check --staged"
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, with_section(existing.as_deref(), command))
+        .with_context(|| format!("Unable to write {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+    println!("Installed {}", path.display());
+    Ok(())
+}
+
+fn uninstall(path: &Path) -> Result<()> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            println!("No hook at {}", path.display());
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let Some(rest) = without_section(&content) else {
+        println!("No sourcelink section in {}", path.display());
+        return Ok(());
+    };
+    // Remove the hook entirely if we created it.
+    if rest.trim() == "#!/bin/sh" {
+        fs::remove_file(path)?;
+    } else {
+        fs::write(path, rest)?;
+    }
+    println!("Uninstalled {}", path.display());
+    Ok(())
+}
+
+pub fn hook(command: &HookCommand) -> Result<()> {
+    let path = git::hook_path(HOOK)?;
+    match command {
+        HookCommand::Install { shorten } => install(&path, *shorten),
+        HookCommand::Uninstall => uninstall(&path),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sections() {
+        let created = with_section(None, "sourcelink check --staged");
+        assert_eq!(
+            created,
+            "#!/bin/sh\n# >>> sourcelink >>>\nsourcelink check --staged || exit $?\n# <<< sourcelink <<<\n"
+        );
+        assert_eq!(without_section(&created).unwrap(), "#!/bin/sh\n");
+
+        let existing = "#!/bin/bash\nnpm test";
+        let appended = with_section(Some(existing), "sourcelink check --staged");
+        assert!(appended.starts_with("#!/bin/bash\nnpm test\n# >>> sourcelink >>>\n"));
+        let replaced = with_section(Some(&appended), "sourcelink shorten --staged");
+        assert_eq!(replaced.matches(BEGIN).count(), 1);
+        assert!(replaced.contains("sourcelink shorten --staged"));
+        assert_eq!(
+            without_section(&replaced).unwrap(),
+            "#!/bin/bash\nnpm test\n"
+        );
+        assert_eq!(without_section(existing), None);
+
+        assert!(ends_early("#!/bin/sh\nexec lint-staged\n"));
+        assert!(!ends_early("#!/bin/sh\nlint-staged\n# exit 0\n"));
+    }
+}
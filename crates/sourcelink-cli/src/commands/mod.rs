@@ -0,0 +1,88 @@
+mod check;
+mod expand;
+mod explain;
+mod hook;
+mod rewrite;
+mod scan;
+mod shorten;
+mod undo;
+
+pub use self::{
+    check::check,
+    expand::expand,
+    explain::explain,
+    hook::{hook, HookCommand},
+    rewrite::{Candidate, RewriteOptions},
+    scan::{scan, scan_files, scan_source, FileReport, FoundLink, ScanOptions},
+    shorten::{shorten, ShortenOptions},
+    undo::{undo, UndoOptions},
+};
+
+use crate::{
+    config::Config,
+    lang::Lang,
+    parser::{Comment, LineIndex},
+    source::SourceFile,
+};
+use anyhow::Result;
+use std::{
+    fs,
+    ops::Range,
+    path::{Component, Path, PathBuf},
+};
+
+/// Read and decode a file, failing if it can't be read and logging why it is
+/// skipped if it can't be decoded.
+fn read_source(path: &Path, config: &Config) -> Result<Option<SourceFile>> {
+    Ok(decode_source(path, &fs::read(path)?, config))
+}
+
+/// Decode a file read from `path`, logging why it is skipped if it can't be.
+fn decode_source(path: &Path, bytes: &[u8], config: &Config) -> Option<SourceFile> {
+    decode_change(path, bytes, config).unwrap_or_else(|err| {
+        log::warn!("Skipping {}: {err}", path.display());
+        None
+    })
+}
+
+/// Decode a file read from `path`, failing if it looks like source code but
+/// can't be decoded, so that checking changes never passes without looking at
+/// them. `None` for other files that can't be.
+fn decode_change(path: &Path, bytes: &[u8], config: &Config) -> Result<Option<SourceFile>> {
+    let filename = path.to_string_lossy();
+    match SourceFile::decode(bytes, &filename, config) {
+        Ok(file) => {
+            if file.encoding != encoding_rs::UTF_8 || file.bom {
+                log::debug!(
+                    "Decoding {filename} as {} ({})",
+                    file.encoding.name(),
+                    file.source
+                );
+            }
+            Ok(Some(file))
+        }
+        Err(err) if Lang::detect_path(&filename, config).is_some() => Err(err.into()),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Whether `path` is one of `paths` or inside one of them.
+fn within(path: &Path, paths: &[PathBuf]) -> bool {
+    let normal = |path: &Path| -> PathBuf {
+        path.components()
+            .filter(|component| !matches!(component, Component::CurDir))
+            .collect()
+    };
+    paths
+        .iter()
+        .any(|dir| normal(path).starts_with(normal(dir)))
+}
+
+/// Whether a comment overlaps any of the 1-based line ranges `lines`.
+fn overlaps(index: &LineIndex, comment: &Comment, lines: &[Range<usize>]) -> bool {
+    let (first, _) = index.line_col(comment.start());
+    let (last, _) = index.line_col(comment.end());
+    lines
+        .iter()
+        .any(|range| range.start <= last && first < range.end)
+}
@@ -0,0 +1,701 @@
+use super::{decode_change, overlaps, read_source, within};
+use crate::{
+    config::Config,
+    diagnostic::{Diagnostic, Level},
+    directives::Directives,
+    doc::DocFormat,
+    error::SourcelinkError,
+    git::{self, Change, DiffBase},
+    journal::Journal,
+    lang::Lang,
+    links::find_comment_links,
+    parser::{LineIndex, SpanKind},
+    reflow,
+    rewrite::{apply, escape_string, unified_diff, Replacement},
+    source::SourceFile,
+    walk::collect_files,
+};
+use anyhow::{Context, Result};
+use clap::Args;
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, IsTerminal, Read, Write},
+    ops::Range,
+    path::{Component, Path, PathBuf},
+};
+
+/// Name given to stdin when no `--filename` is passed.
+const STDIN: &str = "<stdin>";
+
+#[derive(Args, Default, Debug)]
+pub struct RewriteOptions {
+    /// Only rewrite comments in changes staged for commit, updating both the
+    /// index and the working tree
+    #[arg(long)]
+    pub staged: bool,
+    /// Language of the source read from stdin
+    #[arg(long, value_name = "LANG")]
+    pub lang: Option<String>,
+    /// File name to detect the language and encoding of stdin from, and to
+    /// report it as
+    #[arg(long, value_name = "NAME")]
+    pub filename: Option<String>,
+    /// Print a diff of the rewrites instead of making them, without creating
+    /// any links
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Write a patch of the rewrites to a file instead of making them
+    #[arg(long, value_name = "FILE")]
+    pub diff_output: Option<PathBuf>,
+    /// Also rewrite links in string literals, escaping them as needed
+    #[arg(long)]
+    pub strings: bool,
+}
+
+impl RewriteOptions {
+    /// Whether rewrites go into a [`Patch`] instead of the files.
+    fn patch(&self) -> Option<Patch> {
+        (self.dry_run || self.diff_output.is_some()).then(Patch::default)
+    }
+
+    /// Start a journal for a run of `command` on `paths` that rewrites files,
+    /// so that it can be undone.
+    pub(super) fn journal(&self, command: &str, paths: &[PathBuf]) -> Result<Option<Journal>> {
+        let stdin = paths.iter().any(|path| path == Path::new("-"));
+        if stdin || self.patch().is_some() {
+            return Ok(None);
+        }
+        Journal::start(command)
+    }
+}
+
+/// Diff of the rewrites that would be made, for `--dry-run` and
+/// `--diff-output`.
+#[derive(Default)]
+struct Patch {
+    text: String,
+    /// The same diff with each file's part in that file's encoding, which is
+    /// what `git apply` compares against.
+    bytes: Vec<u8>,
+}
+
+impl Patch {
+    fn add(&mut self, filename: &str, file: &SourceFile, replacements: &[Replacement]) {
+        let path: Vec<_> = Path::new(filename)
+            .components()
+            .filter(|component| !matches!(component, Component::CurDir))
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect();
+        let path = path.join("/");
+        self.text
+            .push_str(&unified_diff(&path, &file.text, replacements));
+        if !file.encoding.is_ascii_compatible() {
+            log::warn!(
+                "Leaving {filename} out of the patch, git can't apply patches to {}",
+                file.encoding.name()
+            );
+            return;
+        }
+        let diff = if file.bom {
+            let bom = '\u{FEFF}';
+            let shifted: Vec<_> = replacements
+                .iter()
+                .map(|replacement| Replacement {
+                    start: replacement.start + bom.len_utf8(),
+                    end: replacement.end + bom.len_utf8(),
+                    text: replacement.text.clone(),
+                })
+                .collect();
+            unified_diff(&path, &format!("{bom}{}", file.text), &shifted)
+        } else {
+            unified_diff(&path, &file.text, replacements)
+        };
+        self.bytes.extend_from_slice(&file.encoding.encode(&diff).0);
+    }
+
+    /// Write the diff to `output`, coloured if stdout is a terminal.
+    fn print(&self, output: &mut dyn Write) -> io::Result<()> {
+        let colour = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+        for line in self.text.lines() {
+            let style = match line.chars().next() {
+                _ if !colour => None,
+                _ if line.starts_with("---") || line.starts_with("+++") => Some("1"),
+                Some('@') => Some("36"),
+                Some('-') => Some("31"),
+                Some('+') => Some("32"),
+                _ => None,
+            };
+            match style {
+                Some(style) => writeln!(output, "\x1b[{style}m{line}\x1b[0m")?,
+                None => writeln!(output, "{line}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A link that could be rewritten, and where it was found.
+pub struct Candidate<'a> {
+    pub url: &'a str,
+    pub filename: &'a str,
+    pub line: usize,
+    pub column: usize,
+    /// The lines the link's comment is on, starting at line `comment_line`.
+    pub comment: &'a str,
+    pub comment_line: usize,
+}
+
+/// Replacements found in a source: those of the links, which are reported,
+/// and the edits making them, which write the links the way the comment's
+/// [`DocFormat`] does and replace whole paragraphs when reflowing.
+#[derive(Default)]
+struct Rewrite {
+    /// Links with what `replace` put in their place.
+    links: Vec<Replacement>,
+    /// URL of each of the links, which is only their text if they aren't
+    /// wrapped across lines.
+    urls: Vec<String>,
+    edits: Vec<Replacement>,
+}
+
+/// Links in the comments of `content` that `replace` has a replacement for,
+/// or `None` if the source can't be parsed, which is reported. The language is
+/// detected unless `lang` is given. If `lines` is given, only comments
+/// overlapping those lines are looked at. Links left alone by directives are
+/// skipped, as are links in string literals unless `strings` is set.
+fn find_replacements(
+    config: &Config,
+    filename: &str,
+    lang: Option<&Lang>,
+    content: &str,
+    lines: Option<&[Range<usize>]>,
+    strings: bool,
+    replace: &mut impl FnMut(&Candidate) -> Result<Option<String>>,
+) -> Result<Option<Rewrite>> {
+    let detected;
+    let lang = match lang {
+        Some(lang) => lang,
+        None => match Lang::detect(filename, content, config) {
+            Ok(detection) => {
+                detected = detection.lang;
+                &detected
+            }
+            Err(_) => return Ok(Some(Rewrite::default())),
+        },
+    };
+    let parsed = lang.parser_for(filename, strings).parse_partial(content);
+    if let Some(err) = &parsed.error {
+        eprintln!(
+            "{}",
+            Diagnostic::new(Level::Error, filename, err).render(content)
+        );
+        return Ok(None);
+    }
+    let index = LineIndex::new(content);
+    let mut directives = Directives::new(content, &parsed.comments);
+    let mut replacements = vec![];
+    let mut formatted = vec![];
+    let mut urls = vec![];
+    for (i, comment) in parsed.comments.iter().enumerate() {
+        if lines.is_some_and(|lines| !overlaps(&index, comment, lines)) {
+            continue;
+        }
+        let comment_start = content[..comment.start()].rfind('\n').map_or(0, |i| i + 1);
+        let comment_end = content[comment.end()..]
+            .find('\n')
+            .map_or(content.len(), |i| comment.end() + i);
+        let format = DocFormat::of(lang, content, comment);
+        for link in find_comment_links(content, &parsed.comments, i, config.wrap_width()) {
+            let (line, column) = index.line_col(link.start());
+            if directives.suppresses(i, link.start(), line) {
+                continue;
+            }
+            let candidate = Candidate {
+                url: link.url(),
+                filename,
+                line,
+                column,
+                comment: &content[comment_start..comment_end],
+                comment_line: index.line_col(comment_start).0,
+            };
+            let Some(text) = replace(&candidate)? else {
+                continue;
+            };
+            let written = match comment.kind() {
+                SpanKind::String { quote, escape } => escape_string(&text, quote, escape),
+                SpanKind::Comment => {
+                    Some(format.rewrite(content, &parsed.comments, i, &link, text.clone()))
+                }
+            };
+            if let Some(written) = written {
+                formatted.push(Replacement {
+                    start: link.start(),
+                    end: link.end(),
+                    text: written,
+                });
+                replacements.push(Replacement {
+                    start: link.start(),
+                    end: link.end(),
+                    text,
+                });
+                urls.push(link.url().to_owned());
+            }
+        }
+    }
+    let edits = match &config.reflow {
+        Some(reflow) => reflow::reflow(content, &parsed.comments, &formatted, reflow.width),
+        None => formatted,
+    };
+    Ok(Some(Rewrite {
+        links: replacements,
+        urls,
+        edits,
+    }))
+}
+
+impl Rewrite {
+    /// `file:line:column: url -> replacement` for each link.
+    fn describe(&self, filename: &str, content: &str) -> Vec<String> {
+        let index = LineIndex::new(content);
+        self.links
+            .iter()
+            .zip(&self.urls)
+            .map(|(replacement, url)| {
+                let (line, column) = index.line_col(replacement.start);
+                format!("{filename}:{line}:{column}: {url} -> {}", replacement.text)
+            })
+            .collect()
+    }
+}
+
+fn print_replacements(filename: &str, content: &str, rewrite: &Rewrite) {
+    for line in rewrite.describe(filename, content) {
+        println!("{line}");
+    }
+}
+
+/// Record a rewrite of `path` in `journal`. The file is already written by
+/// then, so failing to is only a warning.
+fn record(
+    journal: Option<&Journal>,
+    path: &Path,
+    content: &str,
+    replacements: &[Replacement],
+    bytes: &[u8],
+) {
+    if let Some(journal) = journal {
+        if let Err(err) = journal.record_file(path, content, replacements, bytes) {
+            log::warn!("Unable to record rewrite of {}: {err:#}", path.display());
+        }
+    }
+}
+
+/// Write `file` back to `path` with `replacements` made, recording them in
+/// `journal`.
+fn write_file(
+    path: &Path,
+    file: &SourceFile,
+    replacements: &[Replacement],
+    journal: Option<&Journal>,
+) -> Result<()> {
+    let bytes = file.to_bytes(&apply(&file.text, replacements))?;
+    fs::write(path, &bytes).with_context(|| format!("Unable to write {}", path.display()))?;
+    record(journal, path, &file.text, replacements, &bytes);
+    Ok(())
+}
+
+/// Rewrite a file in the working tree, or add the rewrite to `patch`. Returns
+/// whether it succeeded, having reported why not.
+fn rewrite_file(
+    config: &Config,
+    path: &Path,
+    patch: Option<&mut Patch>,
+    journal: Option<&Journal>,
+    strings: bool,
+    replace: &mut impl FnMut(&Candidate) -> Result<Option<String>>,
+) -> Result<bool> {
+    let filename = path.to_string_lossy();
+    let file = match read_source(path, config) {
+        Ok(Some(file)) => file,
+        Ok(None) => return Ok(true),
+        Err(err) => {
+            log::error!("{filename}: {err}");
+            return Ok(false);
+        }
+    };
+    let Some(rewrite) =
+        find_replacements(config, &filename, None, &file.text, None, strings, replace)?
+    else {
+        return Ok(false);
+    };
+    if rewrite.links.is_empty() {
+        return Ok(true);
+    }
+    if let Some(patch) = patch {
+        patch.add(&filename, &file, &rewrite.edits);
+        return Ok(true);
+    }
+    print_replacements(&filename, &file.text, &rewrite);
+    if let Err(err) = write_file(path, &file, &rewrite.edits, journal) {
+        log::error!("{filename}: {err}");
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Rewrite the staged lines of a file in the index, then make the same
+/// replacements in the working tree, or add the rewrite of the index to
+/// `patch`. Returns whether it succeeded, having reported why not.
+fn rewrite_staged(
+    config: &Config,
+    change: &Change,
+    patch: Option<&mut Patch>,
+    journal: Option<&Journal>,
+    strings: bool,
+    replace: &mut impl FnMut(&Candidate) -> Result<Option<String>>,
+) -> Result<bool> {
+    let filename = change.path.to_string_lossy();
+    let staged = match git::read_index(&filename) {
+        Ok(staged) => staged,
+        Err(err) => {
+            log::error!("{filename}: {err}");
+            return Ok(false);
+        }
+    };
+    let file = match decode_change(&change.path, &staged, config) {
+        Ok(Some(file)) => file,
+        Ok(None) => return Ok(true),
+        Err(err) => {
+            log::error!("{filename}: {err}");
+            return Ok(false);
+        }
+    };
+    let lines = Some(change.lines.as_slice());
+    let Some(rewrite) =
+        find_replacements(config, &filename, None, &file.text, lines, strings, replace)?
+    else {
+        return Ok(false);
+    };
+    if rewrite.links.is_empty() {
+        return Ok(true);
+    }
+    if let Some(patch) = patch {
+        patch.add(&filename, &file, &rewrite.edits);
+        return Ok(true);
+    }
+    print_replacements(&filename, &file.text, &rewrite);
+    let rewritten = match file.to_bytes(&apply(&file.text, &rewrite.edits)) {
+        Ok(rewritten) => rewritten,
+        Err(err) => {
+            log::error!("{filename}: {err}");
+            return Ok(false);
+        }
+    };
+    if let Err(err) = git::write_index(&filename, &rewritten) {
+        log::error!("{filename}: {err}");
+        return Ok(false);
+    }
+    // A file without unstaged changes can take the index contents as they
+    // are. Otherwise the same links are replaced wherever they are, leaving
+    // the unstaged changes alone.
+    let result = match fs::read(&change.path) {
+        Ok(worktree) if worktree == staged => match fs::write(&change.path, &rewritten) {
+            Ok(()) => {
+                record(
+                    journal,
+                    &change.path,
+                    &file.text,
+                    &rewrite.edits,
+                    &rewritten,
+                );
+                Ok(())
+            }
+            Err(err) => Err(err.into()),
+        },
+        Ok(_) => {
+            let urls: HashMap<&str, &str> = rewrite
+                .urls
+                .iter()
+                .zip(&rewrite.links)
+                .map(|(url, replacement)| (url.as_str(), replacement.text.as_str()))
+                .collect();
+            let worktree = match read_source(&change.path, config) {
+                Ok(Some(worktree)) => worktree,
+                Ok(None) => return Ok(false),
+                Err(err) => {
+                    log::error!("{filename}: {err}");
+                    return Ok(false);
+                }
+            };
+            let mut replace =
+                |candidate: &Candidate| Ok(urls.get(candidate.url).map(|text| text.to_string()));
+            match find_replacements(
+                config,
+                &filename,
+                None,
+                &worktree.text,
+                None,
+                strings,
+                &mut replace,
+            )? {
+                Some(rewrite) => write_file(&change.path, &worktree, &rewrite.edits, journal),
+                None => return Ok(false),
+            }
+        }
+        Err(err) => Err(err.into()),
+    };
+    if let Err(err) = result {
+        log::error!("{filename}: {err}");
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Rewrite a source read from stdin, or `None` if it can't be, having
+/// reported why not. With a `patch` the rewrite is added to it and the source
+/// is left as it is.
+fn rewrite_buffer(
+    config: &Config,
+    options: &RewriteOptions,
+    input: &[u8],
+    patch: Option<&mut Patch>,
+    replace: &mut impl FnMut(&Candidate) -> Result<Option<String>>,
+) -> Result<Option<Vec<u8>>> {
+    let filename = options.filename.as_deref().unwrap_or(STDIN);
+    let lang = match &options.lang {
+        Some(name) => Some(
+            Lang::from_name(name, config)
+                .ok_or_else(|| SourcelinkError::UnknownLanguageName(name.to_owned()))?,
+        ),
+        None => None,
+    };
+    let file = match SourceFile::decode(input, filename, config) {
+        Ok(file) => file,
+        Err(err) => {
+            log::error!("{filename}: {err}");
+            return Ok(None);
+        }
+    };
+    if lang.is_none() && Lang::detect(filename, &file.text, config).is_err() {
+        log::warn!("Unable to determine language of {filename}, pass --lang or --filename");
+    }
+    let Some(rewrite) = find_replacements(
+        config,
+        filename,
+        lang.as_ref(),
+        &file.text,
+        None,
+        options.strings,
+        replace,
+    )?
+    else {
+        return Ok(None);
+    };
+    if rewrite.links.is_empty() {
+        return Ok(Some(input.to_vec()));
+    }
+    if let Some(patch) = patch {
+        patch.add(filename, &file, &rewrite.edits);
+        return Ok(Some(input.to_vec()));
+    }
+    for line in rewrite.describe(filename, &file.text) {
+        log::debug!("{line}");
+    }
+    match file.to_bytes(&apply(&file.text, &rewrite.edits)) {
+        Ok(output) => Ok(Some(output)),
+        Err(err) => {
+            log::error!("{filename}: {err}");
+            Ok(None)
+        }
+    }
+}
+
+/// Rewrite stdin to `stdout`. Whatever goes wrong, the input is still written
+/// out, unchanged, so that an editor filtering a buffer doesn't lose it. A dry
+/// run writes nothing, leaving stdout to the diff.
+fn rewrite_stdin(
+    config: &Config,
+    options: &RewriteOptions,
+    stdin: &mut dyn Read,
+    stdout: &mut dyn Write,
+    patch: Option<&mut Patch>,
+    replace: &mut impl FnMut(&Candidate) -> Result<Option<String>>,
+) -> Result<bool> {
+    let mut input = vec![];
+    stdin.read_to_end(&mut input)?;
+    let result = rewrite_buffer(config, options, &input, patch, replace);
+    if !options.dry_run {
+        let output = match &result {
+            Ok(Some(output)) => output,
+            _ => &input,
+        };
+        stdout.write_all(output)?;
+    }
+    Ok(result?.is_some())
+}
+
+/// Run `setup`, such as connecting to the server, before rewriting `paths`.
+/// If it fails while filtering stdin, the input is copied to `stdout`
+/// unchanged first, as [`rewrite_stdin`] does for later errors.
+pub(super) fn echo_on_error<T>(
+    paths: &[PathBuf],
+    options: &RewriteOptions,
+    stdin: &mut dyn Read,
+    stdout: &mut dyn Write,
+    setup: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    let result = setup();
+    if result.is_err() && paths == [Path::new("-")] && !options.dry_run {
+        io::copy(stdin, stdout)?;
+    }
+    result
+}
+
+/// Replace links in the comments of the files under `paths` with whatever
+/// `replace` returns for them, writing each file back in its own encoding.
+/// A path of `-` filters `input`, normally stdin, to `output`, normally
+/// stdout, instead. With `--dry-run` or `--diff-output` nothing is rewritten,
+/// and a diff is written to `output` or the file instead. Rewrites of files
+/// are recorded in `journal`, if given.
+///
+/// Files that fail to parse or can't be written back are reported and left
+/// untouched.
+pub fn rewrite_links(
+    config: &Config,
+    paths: &[PathBuf],
+    options: &RewriteOptions,
+    journal: Option<&Journal>,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+    mut replace: impl FnMut(&Candidate) -> Result<Option<String>>,
+) -> Result<()> {
+    let stdin = paths.iter().any(|path| path == Path::new("-"));
+    echo_on_error(paths, options, input, output, || {
+        if stdin && (paths.len() > 1 || options.staged) {
+            return Err(SourcelinkError::StdinCombined.into());
+        }
+        if !stdin {
+            if options.lang.is_some() {
+                return Err(SourcelinkError::StdinOnly("--lang".to_owned()).into());
+            }
+            if options.filename.is_some() {
+                return Err(SourcelinkError::StdinOnly("--filename".to_owned()).into());
+            }
+        }
+        Ok(())
+    })?;
+    let mut patch = options.patch();
+    let mut failed = 0;
+    if stdin {
+        if !rewrite_stdin(config, options, input, output, patch.as_mut(), &mut replace)? {
+            failed += 1;
+        }
+    } else if options.staged {
+        let mut changes = git::changes(DiffBase::Index)?;
+        changes.retain(|change| within(&change.path, paths));
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        for change in &changes {
+            if !rewrite_staged(
+                config,
+                change,
+                patch.as_mut(),
+                journal,
+                options.strings,
+                &mut replace,
+            )? {
+                failed += 1;
+            }
+        }
+    } else {
+        for path in collect_files(paths, config)? {
+            if !rewrite_file(
+                config,
+                &path,
+                patch.as_mut(),
+                journal,
+                options.strings,
+                &mut replace,
+            )? {
+                failed += 1;
+            }
+        }
+    }
+    if let Some(journal) = journal.filter(|journal| journal.started()) {
+        eprintln!(
+            "Recorded as run {}, run `sourcelink undo` to revert it",
+            journal.id()
+        );
+    }
+    if let Some(patch) = &patch {
+        if options.dry_run {
+            patch.print(output)?;
+        }
+        if let Some(output) = &options.diff_output {
+            fs::write(output, &patch.bytes)
+                .with_context(|| format!("Unable to write {}", output.display()))?;
+        }
+    }
+    if failed > 0 {
+        Err(SourcelinkError::RewriteFailed(failed).into())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn buffer() {
+        let config = Config::default();
+        let mut replace = |candidate: &Candidate| Ok(Some(candidate.url.replace("long", "short")));
+        let options = RewriteOptions {
+            lang: Some("python".to_owned()),
+            ..Default::default()
+        };
+        let input = b"# https://long.example\nx = 'https://long.example'\n";
+        let output = rewrite_buffer(&config, &options, input, None, &mut replace).unwrap();
+        assert_eq!(
+            output.unwrap(),
+            b"# https://short.example\nx = 'https://long.example'\n"
+        );
+
+        let options = RewriteOptions {
+            filename: Some("main.c".to_owned()),
+            ..Default::default()
+        };
+        let malformed = b"// caf\xE9 https://long.example\n";
+        let output = rewrite_buffer(&config, &options, malformed, None, &mut replace).unwrap();
+        assert!(output.is_none());
+
+        // Without a hint there is nothing to go on.
+        let output = rewrite_buffer(
+            &config,
+            &RewriteOptions::default(),
+            input,
+            None,
+            &mut replace,
+        );
+        assert_eq!(output.unwrap().unwrap(), input);
+    }
+
+    #[test]
+    fn go_strings() {
+        let config = Config::default();
+        let mut replace = |candidate: &Candidate| Ok(Some(candidate.url.replace("long", "short")));
+        let options = RewriteOptions {
+            lang: Some("go".to_owned()),
+            strings: true,
+            ..Default::default()
+        };
+        let input =
+            b"var a = \"https://long.example/it's\"\nvar b = `https://long.example/\"x\"`\n";
+        let output = rewrite_buffer(&config, &options, input, None, &mut replace).unwrap();
+        assert_eq!(
+            output.unwrap(),
+            b"var a = \"https://short.example/it's\"\nvar b = `https://short.example/\"x\"`\n"
+        );
+    }
+}
@@ -0,0 +1,510 @@
+use super::{decode_change, decode_source, overlaps, within};
+use crate::{
+    cache::{self, Cache, CacheEntry, PARSER_VERSION},
+    config::Config,
+    diagnostic::{Diagnostic, Level},
+    directives::Directives,
+    error::SourcelinkError,
+    git::{self, Change, DiffBase},
+    lang::{Detection, Lang},
+    links::find_comment_links,
+    parser::LineIndex,
+    walk::collect_files,
+};
+use anyhow::Result;
+use clap::Args;
+use rayon::{prelude::*, ThreadPoolBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+#[derive(Args, Default, Debug)]
+pub struct ScanOptions {
+    /// Report links found before a parse error instead of skipping the file
+    #[arg(long)]
+    pub lenient: bool,
+    /// Treat unexpected input the lexer recovered from as an error
+    #[arg(long)]
+    pub strict: bool,
+    /// Number of files to parse at once, defaulting to the number of CPUs
+    #[arg(short, long)]
+    pub jobs: Option<usize>,
+    /// Parse every file, rather than reusing results for unchanged ones
+    #[arg(long)]
+    pub no_cache: bool,
+    /// Only scan comments in changes staged for commit, as staged
+    #[arg(long, conflicts_with = "since")]
+    pub staged: bool,
+    /// Only scan comments in changes made since a revision
+    #[arg(long, value_name = "REV")]
+    pub since: Option<String>,
+    /// Scan the files of a commit instead of the working tree
+    #[arg(long, value_name = "REV", conflicts_with_all = ["staged", "since"])]
+    pub rev: Option<String>,
+    /// Also scan string literals, such as error messages pointing to docs
+    #[arg(long)]
+    pub strings: bool,
+}
+
+impl ScanOptions {
+    fn diff_base(&self) -> Option<DiffBase<'_>> {
+        if self.staged {
+            Some(DiffBase::Index)
+        } else {
+            self.since.as_deref().map(DiffBase::Rev)
+        }
+    }
+}
+
+/// Lexer errors per language, for spotting specs that are missing tokens.
+#[derive(Default)]
+struct LexerStats(BTreeMap<String, (usize, usize)>);
+
+impl LexerStats {
+    fn record(&mut self, lang: &str, errors: usize) {
+        if errors > 0 {
+            let (files, total) = self.0.entry(lang.to_owned()).or_default();
+            *files += 1;
+            *total += errors;
+        }
+    }
+
+    fn log(&self) {
+        for (lang, (files, errors)) in &self.0 {
+            log::info!("{lang}: {errors} lexer error(s) in {files} file(s)");
+        }
+    }
+}
+
+/// A link found by [`scan_source`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct FoundLink {
+    pub line: usize,
+    pub column: usize,
+    pub url: String,
+}
+
+/// Result of scanning one file. Reports are printed once every file is done,
+/// so output doesn't depend on which worker finished first.
+#[derive(Default, Debug)]
+pub struct FileReport {
+    pub filename: String,
+    pub lang: String,
+    pub links: Vec<FoundLink>,
+    /// Rendered diagnostics, for stderr.
+    pub diagnostics: Vec<String>,
+    /// Whether the file was skipped because of an error.
+    pub failed: bool,
+    pub lexer_errors: usize,
+}
+
+/// Find the links in the comments of a source, and its string literals with
+/// `--strings`, or `None` if its language is unknown.
+///
+/// A source that fails to parse is reported and skipped, unless `lenient` is
+/// set, in which case the links found before the error are kept. Lexer errors
+/// are only reported in verbose output, or as errors when `strict`. If `lines`
+/// is given, only comments overlapping those lines are looked at. Links left
+/// alone by `sourcelink:` directives are skipped, and directives that leave
+/// nothing alone are warned about.
+pub fn scan_source(
+    config: &Config,
+    filename: &str,
+    content: &str,
+    options: &ScanOptions,
+    lines: Option<&[Range<usize>]>,
+) -> Option<FileReport> {
+    scan_as(config, filename, filename, content, options, lines)
+}
+
+/// [`scan_source`], detecting the language from `path` but reporting the
+/// source as `filename`.
+fn scan_as(
+    config: &Config,
+    path: &str,
+    filename: &str,
+    content: &str,
+    options: &ScanOptions,
+    lines: Option<&[Range<usize>]>,
+) -> Option<FileReport> {
+    let Ok(Detection { lang, reason }) = Lang::detect(path, content, config) else {
+        log::debug!("Skipping {filename}: unknown language");
+        return None;
+    };
+    log::debug!("Parsing {filename} as {} ({reason})", lang.name());
+    let parsed = lang
+        .parser_for(path, options.strings)
+        .parse_partial(content);
+    let mut report = FileReport {
+        filename: filename.to_owned(),
+        lang: lang.name().to_owned(),
+        lexer_errors: parsed.lexer_errors.len(),
+        ..FileReport::default()
+    };
+    let mut diagnose = |level, err: &anyhow::Error| {
+        report
+            .diagnostics
+            .push(Diagnostic::new(level, filename, err).render(content));
+    };
+    if options.strict || log::log_enabled!(log::Level::Debug) {
+        let level = if options.strict {
+            Level::Error
+        } else {
+            Level::Warning
+        };
+        for err in &parsed.lexer_errors {
+            diagnose(level, &err.clone().into());
+        }
+    }
+    let mut skip = options.strict && !parsed.lexer_errors.is_empty();
+    if let Some(err) = &parsed.error {
+        if options.lenient {
+            diagnose(Level::Warning, err);
+        } else {
+            diagnose(Level::Error, err);
+            skip = true;
+        }
+    }
+    if skip {
+        report.failed = true;
+        return Some(report);
+    }
+    let mut index = None;
+    let mut directives = Directives::new(content, &parsed.comments);
+    for (i, comment) in parsed.comments.iter().enumerate() {
+        let wanted = lines.is_none_or(|lines| {
+            let index = index.get_or_insert_with(|| LineIndex::new(content));
+            overlaps(index, comment, lines)
+        });
+        // Links outside the lines still tell whether directives are used.
+        if !wanted && directives.is_empty() {
+            continue;
+        }
+        for link in find_comment_links(content, &parsed.comments, i, config.wrap_width()) {
+            let index = index.get_or_insert_with(|| LineIndex::new(content));
+            let (line, column) = index.line_col(link.start());
+            if directives.suppresses(i, link.start(), line) || !wanted {
+                continue;
+            }
+            report.links.push(FoundLink {
+                line,
+                column,
+                url: link.url().to_owned(),
+            });
+        }
+    }
+    for diagnostic in directives.diagnostics(filename) {
+        let line = diagnostic.location.map_or(0, |location| location.line);
+        if lines.is_none_or(|lines| lines.iter().any(|range| range.contains(&line))) {
+            report.diagnostics.push(diagnostic.render(content));
+        }
+    }
+    Some(report)
+}
+
+/// Report of a file that couldn't be read, failing the scan so that checks
+/// don't pass without looking at it.
+fn failed_report(filename: &str, err: &anyhow::Error) -> FileReport {
+    FileReport {
+        filename: filename.to_owned(),
+        diagnostics: vec![Diagnostic::new(Level::Error, filename, err).render("")],
+        failed: true,
+        ..FileReport::default()
+    }
+}
+
+impl FileReport {
+    /// Whether the report can be reused for the same content. Reports with
+    /// anything to say besides links are not, so it is said again.
+    fn cacheable(&self) -> bool {
+        !self.failed && self.diagnostics.is_empty() && self.lexer_errors == 0
+    }
+}
+
+/// Identifies everything besides file contents that scan results depend on.
+fn fingerprint(config: &Config, options: &ScanOptions) -> String {
+    cache::hash(
+        format!(
+            "{}\0{PARSER_VERSION}\0{}\0{}\0{}\0{}\0{}",
+            env!("CARGO_PKG_VERSION"),
+            cfg!(feature = "tree-sitter"),
+            options.strict,
+            options.lenient,
+            options.strings,
+            config.raw,
+        )
+        .as_bytes(),
+    )
+}
+
+fn scan_path(
+    config: &Config,
+    path: &Path,
+    options: &ScanOptions,
+    cache: Option<&Cache>,
+) -> Option<(FileReport, Option<(String, CacheEntry)>)> {
+    let filename = path.to_string_lossy();
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) => return Some((failed_report(&filename, &err.into()), None)),
+    };
+    let cached = cache.and_then(|cache| Some((cache, cache.key(path)?, cache::hash(&bytes))));
+    if let Some((cache, key, hash)) = &cached {
+        if let Some(entry) = cache.get(key, hash) {
+            log::debug!("Using cached links for {filename}");
+            return Some((entry.report(&filename), None));
+        }
+    }
+    let file = decode_source(path, &bytes, config)?;
+    let report = scan_source(config, &filename, &file.text, options, None)?;
+    let entry = cached.filter(|_| report.cacheable()).map(|(_, key, hash)| {
+        let entry = CacheEntry {
+            hash,
+            lang: report.lang.clone(),
+            links: report.links.clone(),
+        };
+        (key, entry)
+    });
+    Some((report, entry))
+}
+
+/// Scan the changed lines of a file, reading it from the index for
+/// [`DiffBase::Index`]. A file that can't be read fails.
+fn scan_change(
+    config: &Config,
+    change: &Change,
+    base: DiffBase,
+    options: &ScanOptions,
+) -> Option<FileReport> {
+    let filename = change.path.to_string_lossy();
+    let bytes = match base {
+        DiffBase::Index => git::read_index(&filename),
+        DiffBase::Rev(_) => fs::read(&change.path).map_err(Into::into),
+    };
+    let file = match bytes.and_then(|bytes| decode_change(&change.path, &bytes, config)) {
+        Ok(file) => file?,
+        Err(err) => return Some(failed_report(&filename, &err)),
+    };
+    scan_source(config, &filename, &file.text, options, Some(&change.lines))
+}
+
+/// Blobs read from git at once, to bound memory use on large trees.
+const BLOB_BATCH: usize = 1024;
+
+/// Scan the files under `paths` in the tree of `rev`, reading them from the
+/// object database. Reports name files as `path@rev`.
+fn scan_rev(
+    config: &Config,
+    rev: &str,
+    paths: &[PathBuf],
+    options: &ScanOptions,
+) -> Result<Vec<FileReport>> {
+    let mut entries = git::tree(rev, paths)?;
+    // Extensionless files may still turn out to be scripts.
+    entries.retain(|entry| {
+        Lang::detect_path(&entry.path, config).is_some()
+            || Path::new(&entry.path).extension().is_none()
+    });
+    let mut reports = vec![];
+    for batch in entries.chunks(BLOB_BATCH) {
+        let ids: Vec<_> = batch.iter().map(|entry| entry.id.as_str()).collect();
+        let blobs = git::read_blobs(&ids)?;
+        reports.par_extend(batch.par_iter().zip(blobs).filter_map(|(entry, blob)| {
+            let path = Path::new(&entry.path);
+            let file = decode_source(path, &blob, config)?;
+            let filename = format!("{}@{rev}", entry.path);
+            scan_as(config, &entry.path, &filename, &file.text, options, None)
+        }));
+    }
+    Ok(reports)
+}
+
+/// Scan the files under `paths` in parallel, returning reports in path order.
+/// Files unchanged since they were added to `cache` are not parsed again, and
+/// new results are added to it.
+///
+/// With `--staged` or `--since`, only comments in changed lines of changed
+/// files are scanned, and with `--rev` the files of a commit. The cache is not
+/// used for either.
+pub fn scan_files(
+    config: &Config,
+    paths: &[PathBuf],
+    options: &ScanOptions,
+    mut cache: Option<&mut Cache>,
+) -> Result<Vec<FileReport>> {
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(options.jobs.unwrap_or(0))
+        .build()?;
+    if let Some(rev) = &options.rev {
+        return pool.install(|| scan_rev(config, rev, paths, options));
+    }
+    if let Some(base) = options.diff_base() {
+        let mut changes = git::changes(base)?;
+        changes.retain(|change| within(&change.path, paths));
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        return Ok(pool.install(|| {
+            changes
+                .par_iter()
+                .filter_map(|change| scan_change(config, change, base, options))
+                .collect()
+        }));
+    }
+    let files = collect_files(paths, config)?;
+    let shared = cache.as_deref();
+    let results: Vec<_> = pool.install(|| {
+        files
+            .par_iter()
+            .filter_map(|path| scan_path(config, path, options, shared))
+            .collect()
+    });
+    let mut reports = Vec::with_capacity(results.len());
+    for (report, entry) in results {
+        if let (Some(cache), Some((key, entry))) = (cache.as_deref_mut(), entry) {
+            cache.insert(key, entry);
+        }
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+/// Scan the files under `paths` using the repository's cache, printing
+/// diagnostics as they would be for `scan`. Returns the reports and the number
+/// of files that failed.
+pub(super) fn scan_reports(
+    config: &Config,
+    paths: &[PathBuf],
+    options: &ScanOptions,
+) -> Result<(Vec<FileReport>, usize)> {
+    let mut cache = if options.no_cache || options.diff_base().is_some() || options.rev.is_some() {
+        None
+    } else {
+        Cache::discover(&fingerprint(config, options))?
+    };
+    let reports = scan_files(config, paths, options, cache.as_mut())?;
+    if let Some(cache) = &mut cache {
+        if let Err(err) = cache.save() {
+            log::warn!("{err:#}");
+        }
+    }
+    let mut failed = 0;
+    let mut stats = LexerStats::default();
+    for report in &reports {
+        stats.record(&report.lang, report.lexer_errors);
+        for diagnostic in &report.diagnostics {
+            eprintln!("{diagnostic}");
+        }
+        if report.failed {
+            failed += 1;
+        }
+    }
+    stats.log();
+    Ok((reports, failed))
+}
+
+/// Print every link found in the comments of the files under `paths`.
+pub fn scan(config: &Config, paths: &[PathBuf], options: &ScanOptions) -> Result<()> {
+    let (reports, failed) = scan_reports(config, paths, options)?;
+    for report in &reports {
+        for FoundLink { line, column, url } in &report.links {
+            println!("{}:{line}:{column}: {url}", report.filename);
+        }
+    }
+    if failed > 0 {
+        Err(SourcelinkError::ParseFailed(failed).into())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn parallel_order() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..20 {
+            let content = format!("// https://example.com/{i}\nint x;\n");
+            fs::write(dir.path().join(format!("{i:02}.c")), content).unwrap();
+        }
+        fs::write(dir.path().join("notes.txt"), "https://example.com").unwrap();
+        let options = ScanOptions {
+            jobs: Some(4),
+            ..ScanOptions::default()
+        };
+        let reports =
+            scan_files(&Config::default(), &[dir.path().to_owned()], &options, None).unwrap();
+        let urls: Vec<_> = reports
+            .iter()
+            .map(|report| report.links[0].url.clone())
+            .collect();
+        let expected: Vec<_> = (0..20)
+            .map(|i| format!("https://example.com/{i}"))
+            .collect();
+        assert_eq!(urls, expected);
+        assert_eq!(reports[3].links[0].column, 4);
+    }
+
+    #[test]
+    fn changed_lines() {
+        let content = "// https://example.com/a\n/*\n * https://example.com/b\n */\nint x;\n";
+        let options = ScanOptions::default();
+        let scan = |lines: Range<usize>| -> Vec<String> {
+            scan_source(
+                &Config::default(),
+                "a.c",
+                content,
+                &options,
+                Some(std::slice::from_ref(&lines)),
+            )
+            .unwrap()
+            .links
+            .into_iter()
+            .map(|link| link.url)
+            .collect()
+        };
+        assert_eq!(scan(1..2), vec!["https://example.com/a"]);
+        assert_eq!(scan(4..6), vec!["https://example.com/b"]);
+        assert!(scan(5..6).is_empty());
+        assert!(within(Path::new("src/a.c"), &[PathBuf::from("./src")]));
+        assert!(!within(Path::new("srcs/a.c"), &[PathBuf::from("src")]));
+    }
+
+    #[test]
+    fn unreadable_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let change = Change {
+            path: dir.path().join("gone b.c"),
+            lines: vec![],
+        };
+        let options = ScanOptions::default();
+        let report =
+            scan_change(&Config::default(), &change, DiffBase::Rev("HEAD"), &options).unwrap();
+        assert!(report.failed);
+        assert!(report.diagnostics[0].contains("gone b.c"));
+    }
+
+    #[test]
+    fn strict_valid_code() {
+        let options = ScanOptions {
+            strict: true,
+            ..ScanOptions::default()
+        };
+        let content = include_str!("../../../../test/lifetimes.rs");
+        let report =
+            scan_source(&Config::default(), "lifetimes.rs", content, &options, None).unwrap();
+        assert!(!report.failed, "{:?}", report.diagnostics);
+        assert_eq!(report.lexer_errors, 0);
+        assert_eq!(report.links.len(), 4);
+
+        let content = "const re = /\"/; // https://example.com/a\n";
+        let report = scan_source(&Config::default(), "main.js", content, &options, None).unwrap();
+        assert!(!report.failed, "{:?}", report.diagnostics);
+        assert_eq!(report.links.len(), 1);
+    }
+}
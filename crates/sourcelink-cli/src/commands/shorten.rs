@@ -0,0 +1,341 @@
+use super::rewrite::{echo_on_error, rewrite_links, Candidate, RewriteOptions};
+use crate::{
+    client::Client,
+    config::{host, Config},
+    error::SourcelinkError,
+};
+use anyhow::Result;
+use clap::Args;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, BufRead, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Lines of the comment shown either side of a link under review.
+const REVIEW_CONTEXT: usize = 2;
+
+#[derive(Args, Default, Debug)]
+pub struct ShortenOptions {
+    #[command(flatten)]
+    pub rewrite: RewriteOptions,
+    /// Ask before shortening each link
+    #[arg(short, long)]
+    pub interactive: bool,
+}
+
+/// Answer to the review of a link.
+#[derive(PartialEq, Debug)]
+enum Decision {
+    /// Shorten the link, with the given slug if any.
+    Accept(Option<String>),
+    Skip,
+    SkipDomain,
+    Quit,
+}
+
+/// A trimmed line of input, or `None` at the end of it.
+fn read_line(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    Ok((input.read_line(&mut line)? > 0).then(|| line.trim().to_owned()))
+}
+
+/// Show a link in its comment and ask what to do with it, offering to skip
+/// `domain` if there is one. Slugs are checked with `valid_slug`.
+fn review(
+    candidate: &Candidate,
+    domain: Option<&str>,
+    valid_slug: impl Fn(&str) -> bool,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<Decision> {
+    writeln!(
+        output,
+        "{}:{}:{}: {}",
+        candidate.filename, candidate.line, candidate.column, candidate.url
+    )?;
+    let lines = candidate.comment.lines();
+    let width = (candidate.comment_line + lines.clone().count())
+        .to_string()
+        .len();
+    for (number, line) in (candidate.comment_line..).zip(lines) {
+        if number + REVIEW_CONTEXT < candidate.line || number > candidate.line + REVIEW_CONTEXT {
+            continue;
+        }
+        let marker = if number == candidate.line { '>' } else { ' ' };
+        let line = line.trim_end_matches('\r');
+        writeln!(output, "{marker} {number:>width$} | {line}")?;
+    }
+    let skip_domain = domain.map_or(String::new(), |domain| format!(", skip [d]omain {domain}"));
+    loop {
+        write!(
+            output,
+            "[a]ccept, [s]kip{skip_domain}, [e]dit slug, [q]uit? "
+        )?;
+        output.flush()?;
+        let Some(answer) = read_line(input)? else {
+            return Ok(Decision::Quit);
+        };
+        match answer.as_str() {
+            "a" => return Ok(Decision::Accept(None)),
+            "s" => return Ok(Decision::Skip),
+            "d" if domain.is_some() => return Ok(Decision::SkipDomain),
+            "q" => return Ok(Decision::Quit),
+            "e" => loop {
+                write!(output, "Slug (empty to go back): ")?;
+                output.flush()?;
+                let Some(slug) = read_line(input)? else {
+                    return Ok(Decision::Quit);
+                };
+                if slug.is_empty() {
+                    break;
+                }
+                if valid_slug(&slug) {
+                    return Ok(Decision::Accept(Some(slug)));
+                }
+                writeln!(
+                    output,
+                    "Slugs can only contain letters, digits, `-` and `_`"
+                )?;
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Replace links in the comments of the files under `paths` with short links,
+/// creating one per distinct normalized URL. A dry run uses placeholders instead of
+/// creating any.
+///
+/// Interactively, each link is shown and can be skipped, have its domain
+/// skipped from then on, or be given a slug of its own. The answers are read
+/// from `input`, which is otherwise the source read for `-` and filtered to
+/// `output`.
+pub fn shorten(
+    config: &Config,
+    paths: &[PathBuf],
+    options: &ShortenOptions,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<()> {
+    let (server, client) = echo_on_error(paths, &options.rewrite, input, output, || {
+        if options.interactive && paths.iter().any(|path| path == Path::new("-")) {
+            return Err(SourcelinkError::StdinCombined.into());
+        }
+        let server = config.server.as_ref().ok_or(SourcelinkError::NoServer)?;
+        // A dry run doesn't talk to the server, so doesn't need an API key.
+        let client = if options.rewrite.dry_run {
+            None
+        } else {
+            Some(Client::new(config)?)
+        };
+        Ok((server, client))
+    })?;
+    let journal = options.rewrite.journal("shorten", paths)?;
+    let mut shortened: HashMap<String, String> = HashMap::new();
+    let mut skipped: HashSet<String> = HashSet::new();
+    let mut skipped_domains: HashSet<String> = HashSet::new();
+    let mut quit = false;
+    let mut empty = io::empty();
+    let (mut answers, stdin): (Option<&mut dyn BufRead>, &mut dyn Read) = if options.interactive {
+        (Some(input), &mut empty)
+    } else {
+        (None, input)
+    };
+    let valid_slug = |slug: &str| server.link_id(&server.short_url(slug)).is_some();
+    rewrite_links(
+        config,
+        paths,
+        &options.rewrite,
+        journal.as_ref(),
+        stdin,
+        output,
+        |candidate| {
+            let url = candidate.url;
+            if !config.verdict(url).shorten() {
+                return Ok(None);
+            }
+            // Equivalent links share a short link to the normalized URL.
+            let target = config.shorten.normalize.apply(url);
+            if let Some(short) = shortened.get(&target) {
+                return Ok(Some(short.clone()));
+            }
+            let domain = host(url);
+            if quit
+                || skipped.contains(url)
+                || domain
+                    .as_ref()
+                    .is_some_and(|domain| skipped_domains.contains(domain))
+            {
+                return Ok(None);
+            }
+            let short = loop {
+                let slug = if let Some(answers) = answers.as_mut() {
+                    let decision = review(
+                        candidate,
+                        domain.as_deref(),
+                        valid_slug,
+                        answers,
+                        &mut io::stderr(),
+                    )?;
+                    match decision {
+                        Decision::Accept(slug) => slug,
+                        Decision::Skip => {
+                            skipped.insert(url.to_owned());
+                            return Ok(None);
+                        }
+                        Decision::SkipDomain => {
+                            let domain = domain.unwrap();
+                            if let Err(err) = config.remember_skip_domain(&domain) {
+                                log::warn!("Unable to remember to skip {domain}: {err:#}");
+                            }
+                            skipped_domains.insert(domain);
+                            return Ok(None);
+                        }
+                        Decision::Quit => {
+                            quit = true;
+                            return Ok(None);
+                        }
+                    }
+                } else {
+                    None
+                };
+                let Some(client) = &client else {
+                    let placeholder = format!("<new-{}>", shortened.len() + 1);
+                    break server.short_url(slug.as_deref().unwrap_or(&placeholder));
+                };
+                match client.create_link(&target, slug.as_deref()) {
+                    Ok(link) => {
+                        if let Some(journal) = &journal {
+                            if let Err(err) = journal.record_link(&link.id) {
+                                log::warn!("Unable to record link {}: {err:#}", link.id);
+                            }
+                        }
+                        break client.short_url(&link.id);
+                    }
+                    // The slug may be taken, so let the user pick another.
+                    Err(err) if slug.is_some() => eprintln!("{err:#}"),
+                    Err(err) => return Err(err),
+                }
+            };
+            shortened.insert(target, short.clone());
+            Ok(Some(short))
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn review_link() {
+        let comment = "/*\n * See\n * https://example.com/spec\n */";
+        let candidate = Candidate {
+            url: "https://example.com/spec",
+            filename: "main.c",
+            line: 12,
+            column: 4,
+            comment,
+            comment_line: 10,
+        };
+        let valid = |slug: &str| !slug.contains(' ');
+        let mut output = vec![];
+        let mut input = "x\ne\nmy slug\nspec\n".as_bytes();
+        let decision = review(&candidate, None, valid, &mut input, &mut output).unwrap();
+        assert_eq!(decision, Decision::Accept(Some("spec".to_owned())));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("main.c:12:4: https://example.com/spec\n"));
+        assert!(output.contains("> 12 |  * https://example.com/spec\n"));
+        assert!(output.contains("Slugs can only contain"));
+
+        let mut input = "d\nd\n".as_bytes();
+        let domain = Some("example.com");
+        let decision = review(&candidate, domain, valid, &mut input, &mut vec![]).unwrap();
+        assert_eq!(decision, Decision::SkipDomain);
+        let decision = review(&candidate, None, valid, &mut "d\n".as_bytes(), &mut vec![]);
+        assert_eq!(decision.unwrap(), Decision::Quit);
+    }
+
+    #[test]
+    fn shorten_stdin() {
+        let config =
+            Config::parse("[server]\nurl = \"https://sl.example\"\nproject = \"p\"").unwrap();
+        let patch = tempfile::NamedTempFile::new().unwrap();
+        let options = ShortenOptions {
+            rewrite: RewriteOptions {
+                lang: Some("python".to_owned()),
+                dry_run: true,
+                diff_output: Some(patch.path().to_owned()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut input = "# https://long.example.com/a\n".as_bytes();
+        let mut output = vec![];
+        shorten(
+            &config,
+            &[PathBuf::from("-")],
+            &options,
+            &mut input,
+            &mut output,
+        )
+        .unwrap();
+        // The dry run prints the diff, coloured if stdout is a terminal.
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("# https://sl.example/<new-1>"), "{output}");
+        let patch = std::fs::read_to_string(patch.path()).unwrap();
+        assert!(patch.contains("+# https://sl.example/<new-1>"), "{patch}");
+
+        let options = ShortenOptions {
+            interactive: true,
+            ..options
+        };
+        let mut output = vec![];
+        let result = shorten(
+            &config,
+            &[PathBuf::from("-")],
+            &options,
+            &mut "".as_bytes(),
+            &mut output,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn stdin_without_server() {
+        let input = "# https://long.example.com/a\n";
+        let paths = [PathBuf::from("-")];
+        let options = ShortenOptions {
+            rewrite: RewriteOptions {
+                lang: Some("python".to_owned()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut output = vec![];
+        let result = shorten(
+            &Config::default(),
+            &paths,
+            &options,
+            &mut input.as_bytes(),
+            &mut output,
+        );
+        assert!(result.is_err());
+        assert_eq!(output, input.as_bytes());
+
+        // Without an API key, whether or not one is in the environment.
+        let config =
+            Config::parse("[server]\nurl = \"http://127.0.0.1:9\"\nproject = \"p\"").unwrap();
+        let mut output = vec![];
+        let result = shorten(
+            &config,
+            &paths,
+            &options,
+            &mut input.as_bytes(),
+            &mut output,
+        );
+        assert!(result.is_err());
+        assert_eq!(output, input.as_bytes());
+    }
+}
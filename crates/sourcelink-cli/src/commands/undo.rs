@@ -0,0 +1,198 @@
+use crate::{
+    cache::hash,
+    client::Client,
+    config::Config,
+    error::SourcelinkError,
+    journal::{FileEdits, Run},
+    rewrite::{apply, Replacement},
+    source::SourceFile,
+};
+use anyhow::Result;
+use clap::Args;
+use std::{fs, path::Path};
+
+#[derive(Args, Debug)]
+pub struct UndoOptions {
+    /// Run to undo, the latest one if not given
+    run: Option<String>,
+    /// Also delete the links the run created from the server
+    #[arg(long)]
+    delete_links: bool,
+    /// List the runs that can be undone instead
+    #[arg(long, conflicts_with_all = ["run", "delete_links"])]
+    list: bool,
+}
+
+/// Revert the edits made to a file by a run, as long as it hasn't changed
+/// since. Returns whether it was reverted, having reported why not.
+fn undo_file(config: &Config, root: &Path, file: &FileEdits) -> bool {
+    let path = root.join(&file.path);
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            log::error!("{}: {err}", file.path);
+            return false;
+        }
+    };
+    if hash(&bytes) != file.hash {
+        log::error!("{}: changed since the run, leaving it alone", file.path);
+        return false;
+    }
+    let source = match SourceFile::decode(&bytes, &file.path, config) {
+        Ok(source) => source,
+        Err(err) => {
+            log::error!("{}: {err}", file.path);
+            return false;
+        }
+    };
+    let replacements: Option<Vec<Replacement>> = file
+        .edits
+        .iter()
+        .map(|edit| {
+            let end = edit.offset + edit.after.len();
+            (source.text.get(edit.offset..end) == Some(edit.after.as_str())).then(|| Replacement {
+                start: edit.offset,
+                end,
+                text: edit.before.clone(),
+            })
+        })
+        .collect();
+    let Some(replacements) = replacements else {
+        log::error!("{}: doesn't match the journal, leaving it alone", file.path);
+        return false;
+    };
+    if let Err(err) = source.write(&path, &apply(&source.text, &replacements)) {
+        log::error!("{}: {err}", file.path);
+        return false;
+    }
+    println!("{}: reverted {} link(s)", file.path, replacements.len());
+    true
+}
+
+/// Revert the files rewritten by a `shorten` or `expand` run, optionally
+/// deleting the links it created. Whatever can't be undone is kept in the
+/// journal to try again.
+pub fn undo(config: &Config, options: &UndoOptions) -> Result<()> {
+    let (runs, root) = Run::all()?;
+    if options.list {
+        for run in &runs {
+            println!(
+                "{}  {}  {} file(s), {} link(s) created",
+                run.id,
+                run.command,
+                run.files.len(),
+                run.links.len()
+            );
+        }
+        return Ok(());
+    }
+    let run = match &options.run {
+        Some(id) => runs
+            .iter()
+            .find(|run| &run.id == id)
+            .ok_or_else(|| SourcelinkError::UnknownRun(id.clone()))?,
+        None => runs.last().ok_or(SourcelinkError::NoRuns)?,
+    };
+    // There are only runs inside a repository.
+    undo_run(config, &root.unwrap(), run, options.delete_links)
+}
+
+/// Undo `run` in the repository at `root`. Links are kept in the journal
+/// unless deleted, so they can still be deleted by undoing it again.
+fn undo_run(config: &Config, root: &Path, run: &Run, delete_links: bool) -> Result<()> {
+    let files: Vec<FileEdits> = run
+        .files
+        .iter()
+        .rev()
+        .filter(|file| !undo_file(config, root, file))
+        .cloned()
+        .collect();
+    let mut failed_links = 0;
+    let links = if delete_links && !run.links.is_empty() {
+        let client = Client::new(config)?;
+        let links: Vec<String> = run
+            .links
+            .iter()
+            .filter(|id| match client.delete_link(id) {
+                Ok(()) => false,
+                Err(err) => {
+                    log::error!("{err:#}");
+                    true
+                }
+            })
+            .cloned()
+            .collect();
+        failed_links = links.len();
+        links
+    } else {
+        run.links.clone()
+    };
+    let failed_files = files.len();
+    run.save_remaining(files, links)?;
+    if failed_files > 0 {
+        Err(SourcelinkError::UndoFailed(failed_files).into())
+    } else if failed_links > 0 {
+        Err(SourcelinkError::DeleteFailed(failed_links).into())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::journal::Journal;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    #[test]
+    fn delete_links_after_undo() {
+        let repo = tempfile::tempdir().unwrap();
+        let root = repo.path().canonicalize().unwrap();
+        let dir = root.join("journal");
+        let file = root.join("main.c");
+        let content = "// https://example.com/long\n";
+        let rewritten = "// https://sl.example/abc\n";
+        fs::write(&file, rewritten).unwrap();
+        let journal = Journal::open(&dir, &root, "shorten");
+        let replacements = [Replacement {
+            start: 3,
+            end: 27,
+            text: "https://sl.example/abc".to_owned(),
+        }];
+        journal
+            .record_file(&file, content, &replacements, rewritten.as_bytes())
+            .unwrap();
+        journal.record_link("abc").unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = Config::parse(&format!(
+            "[server]\nurl = \"http://{}\"\nproject = \"p\"\napi_key = \"key\"\n",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+
+        let run = &Run::all_in(&dir).unwrap()[0];
+        undo_run(&config, &root, run, false).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), content);
+        let runs = Run::all_in(&dir).unwrap();
+        assert!(runs[0].files.is_empty());
+        assert_eq!(runs[0].links, vec!["abc"]);
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = String::new();
+            BufReader::new(&stream).read_line(&mut request).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            request
+        });
+        undo_run(&config, &root, &runs[0], true).unwrap();
+        assert!(server.join().unwrap().starts_with("DELETE /api/link/abc "));
+        assert!(Run::all_in(&dir).unwrap().is_empty());
+    }
+}
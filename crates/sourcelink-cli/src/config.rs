@@ -0,0 +1,330 @@
+use crate::{
+    error::*,
+    lang::Lang,
+    rules::{Action, Reason, ShortenConfig, Verdict},
+    source::encoding_for_label,
+    spec::LanguageSpec,
+};
+use anyhow::{Context, Result};
+use encoding_rs::Encoding;
+use globset::{Glob, GlobMatcher};
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use toml_edit::DocumentMut;
+use url::Url;
+
+pub const CONFIG_FILENAME: &str = ".sourcelink.toml";
+
+/// Contents of `.sourcelink.toml`.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Config {
+    /// User-defined languages, checked before the built-in ones.
+    pub languages: Vec<Arc<LanguageSpec>>,
+    /// Languages to use for particular files, checked in order before any
+    /// other detection.
+    pub overrides: Vec<Override>,
+    /// Encodings to use for files that neither have a BOM nor declare one.
+    pub encodings: Vec<EncodingOverride>,
+    /// Link shortening service used by `shorten` and `expand`.
+    pub server: Option<ServerConfig>,
+    /// Which links `shorten` replaces.
+    pub shorten: ShortenConfig,
+    /// Rewrap paragraphs whose links are replaced, if set.
+    pub reflow: Option<ReflowConfig>,
+    /// Column comments are wrapped at, defaulting to the `[reflow]` width. A
+    /// URL is only joined with the next line if its own line reaches it.
+    pub wrap_width: Option<usize>,
+    /// The config as written, so caches can tell when it changed.
+    #[serde(skip)]
+    pub raw: String,
+    /// File the config was loaded from.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReflowConfig {
+    /// Column comment lines are wrapped at.
+    pub width: usize,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ServerConfig {
+    /// Base URL of the server, which short links are made relative to.
+    pub url: String,
+    /// Project new links are created in.
+    pub project: String,
+    /// API key, if not given by `SOURCELINK_API_KEY`.
+    pub api_key: Option<String>,
+}
+
+impl ServerConfig {
+    fn base_url(&self) -> &str {
+        self.url.trim_end_matches('/')
+    }
+
+    pub fn short_url(&self, id: &str) -> String {
+        format!("{}/{id}", self.base_url())
+    }
+
+    /// Id of a short link pointing at this server, if `url` is one.
+    pub fn link_id<'url>(&self, url: &'url str) -> Option<&'url str> {
+        url.strip_prefix(self.base_url())?
+            .strip_prefix('/')
+            .filter(|id| {
+                !id.is_empty()
+                    && id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(try_from = "OverrideEntry")]
+pub struct Override {
+    pub glob: GlobMatcher,
+    pub language: String,
+}
+
+#[derive(Deserialize)]
+struct OverrideEntry {
+    pattern: String,
+    language: String,
+}
+
+impl TryFrom<OverrideEntry> for Override {
+    type Error = globset::Error;
+
+    fn try_from(entry: OverrideEntry) -> Result<Self, Self::Error> {
+        Ok(Self {
+            glob: Glob::new(&entry.pattern)?.compile_matcher(),
+            language: entry.language,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(try_from = "EncodingOverrideEntry")]
+pub struct EncodingOverride {
+    pub glob: GlobMatcher,
+    pub encoding: &'static Encoding,
+}
+
+#[derive(Deserialize)]
+struct EncodingOverrideEntry {
+    pattern: String,
+    encoding: String,
+}
+
+impl TryFrom<EncodingOverrideEntry> for EncodingOverride {
+    type Error = anyhow::Error;
+
+    fn try_from(entry: EncodingOverrideEntry) -> Result<Self, Self::Error> {
+        let encoding = encoding_for_label(&entry.encoding)
+            .ok_or(SourcelinkError::UnknownEncoding(entry.encoding))?;
+        Ok(Self {
+            glob: Glob::new(&entry.pattern)?.compile_matcher(),
+            encoding,
+        })
+    }
+}
+
+impl Config {
+    /// Width that lines a URL was wrapped from reach, if any is configured.
+    pub fn wrap_width(&self) -> Option<usize> {
+        self.wrap_width
+            .or(self.reflow.as_ref().map(|reflow| reflow.width))
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut config: Self = toml::from_str(content)?;
+        config.raw = content.to_owned();
+        for spec in &config.languages {
+            if spec.line_comments.is_empty() && spec.block_comments.is_empty() {
+                return Err(SourcelinkError::InvalidLanguage(spec.name.clone()).into());
+            }
+        }
+        for entry in &config.overrides {
+            if Lang::from_name(&entry.language, &config).is_none() {
+                return Err(SourcelinkError::UnknownLanguageName(entry.language.clone()).into());
+            }
+        }
+        Ok(config)
+    }
+
+    /// Encoding configured for a file, if any.
+    pub fn encoding(&self, filename: &str) -> Option<&'static Encoding> {
+        let path = filename.strip_prefix("./").unwrap_or(filename);
+        self.encodings
+            .iter()
+            .find(|entry| entry.glob.is_match(path))
+            .map(|entry| entry.encoding)
+    }
+
+    /// Whether `shorten` would replace `url`, and why.
+    pub fn verdict(&self, url: &str) -> Verdict {
+        if self
+            .server
+            .as_ref()
+            .is_some_and(|server| server.link_id(url).is_some())
+        {
+            return Verdict {
+                action: Action::Skip,
+                reason: Reason::ShortLink,
+            };
+        }
+        self.shorten.verdict(url)
+    }
+
+    /// Add `domain` to the `skip_domains` in the config file, keeping the rest
+    /// of the file as it is.
+    pub fn remember_skip_domain(&self, domain: &str) -> Result<()> {
+        let path = self
+            .path
+            .as_deref()
+            .context("No config file to remember skipped domains in")?;
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read config {}", path.display()))?;
+        let mut doc: DocumentMut = content
+            .parse()
+            .with_context(|| format!("Invalid config {}", path.display()))?;
+        doc.entry("shorten")
+            .or_insert_with(toml_edit::table)
+            .as_table_like_mut()
+            .context("`shorten` is not a table")?
+            .entry("skip_domains")
+            .or_insert_with(|| toml_edit::value(toml_edit::Array::new()))
+            .as_array_mut()
+            .context("`shorten.skip_domains` is not an array")?
+            .push(domain);
+        fs::write(path, doc.to_string())
+            .with_context(|| format!("Unable to write config {}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read config {}", path.display()))?;
+        let mut config =
+            Self::parse(&content).with_context(|| format!("Invalid config {}", path.display()))?;
+        config.path = Some(path.to_owned());
+        Ok(config)
+    }
+
+    /// Load the config from `path` if given, otherwise from the nearest
+    /// `.sourcelink.toml` in the current directory or its ancestors.
+    pub fn discover(path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) => Self::load(path),
+            None => match find_config(&std::env::current_dir()?) {
+                Some(path) => {
+                    log::debug!("Using config {}", path.display());
+                    Self::load(&path)
+                }
+                None => Ok(Self::default()),
+            },
+        }
+    }
+}
+
+/// Lowercased host of a URL, if it has one.
+pub fn host(url: &str) -> Option<String> {
+    Url::parse(url)
+        .ok()?
+        .host_str()
+        .map(str::to_ascii_lowercase)
+}
+
+fn find_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(CONFIG_FILENAME))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn parse_languages() {
+        let config = Config::parse(indoc! {r##"
+            [[languages]]
+            name = "ini"
+            extensions = ["ini", "cfg"]
+            line_comments = [";", "#"]
+            strings = [{ open = '"', close = '"', raw = true }]
+        "##})
+        .unwrap();
+        assert_eq!(config.languages.len(), 1);
+        let spec = &config.languages[0];
+        assert!(spec.matches_extension("cfg"));
+        assert_eq!(spec.line_comments, vec![";", "#"]);
+        assert_eq!(spec.strings[0].escape(), None);
+        assert!(Config::parse("[[languages]]\nname = \"empty\"").is_err());
+    }
+
+    #[test]
+    fn parse_overrides() {
+        let config = Config::parse(indoc! {r#"
+            [[overrides]]
+            pattern = "bin/*"
+            language = "python"
+        "#})
+        .unwrap();
+        assert!(config.overrides[0].glob.is_match("bin/deploy"));
+        assert!(Config::parse(indoc! {r#"
+            [[overrides]]
+            pattern = "*.inc"
+            language = "cobol"
+        "#})
+        .is_err());
+    }
+
+    #[test]
+    fn parse_encodings() {
+        let config = Config::parse(indoc! {r#"
+            [[encodings]]
+            pattern = "legacy/**/*.c"
+            encoding = "latin1"
+        "#})
+        .unwrap();
+        assert_eq!(
+            config.encoding("./legacy/src/main.c"),
+            Some(encoding_rs::WINDOWS_1252)
+        );
+        assert_eq!(config.encoding("src/main.c"), None);
+        assert!(Config::parse(indoc! {r#"
+            [[encodings]]
+            pattern = "*.c"
+            encoding = "klingon"
+        "#})
+        .is_err());
+    }
+
+    #[test]
+    fn skip_domains() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILENAME);
+        fs::write(
+            &path,
+            "# Links\n[server]\nurl = \"https://s\"\nproject = \"p\"\n",
+        )
+        .unwrap();
+        let config = Config::load(&path).unwrap();
+        assert!(config.verdict("https://spdx.org/licenses/MIT").shorten());
+        config.remember_skip_domain("spdx.org").unwrap();
+        config.remember_skip_domain("w3.org").unwrap();
+        let config = Config::load(&path).unwrap();
+        assert!(config.raw.starts_with("# Links\n"));
+        assert_eq!(config.shorten.skip_domains, vec!["spdx.org", "w3.org"]);
+        assert!(!config.verdict("https://SPDX.org/licenses/MIT").shorten());
+        assert!(!config.verdict("https://www.w3.org/TR/").shorten());
+        assert!(config.verdict("https://notw3.org/").shorten());
+    }
+}
@@ -0,0 +1,155 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::{fmt, path::Path};
+
+/// How a file's language was determined.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Reason {
+    Override,
+    Extension,
+    Filename,
+    Shebang,
+    Modeline,
+    Heuristic,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Override => write!(f, "config override"),
+            Self::Extension => write!(f, "extension"),
+            Self::Filename => write!(f, "file name"),
+            Self::Shebang => write!(f, "shebang"),
+            Self::Modeline => write!(f, "modeline"),
+            Self::Heuristic => write!(f, "content heuristics"),
+        }
+    }
+}
+
+lazy_static! {
+    static ref EMACS_MODELINE: Regex = Regex::new(r"-\*-(.*?)-\*-").unwrap();
+    static ref VIM_MODELINE: Regex =
+        Regex::new(r"(?:^|\s)(?:vi|vim|ex):.*?\b(?:ft|filetype|syntax)=([\w+-]+)").unwrap();
+    static ref CPP_HEURISTIC: Regex = Regex::new(
+        r"(?m)^\s*(?:class\s+\w+|namespace\b|template\s*<|using\s+namespace\b|(?:public|private|protected):)|\bstd::|#include\s*<(?:iostream|string|vector|memory|map)>"
+    )
+    .unwrap();
+    /// Patterns telling extensionless files apart, as (language name, pattern).
+    static ref HEURISTICS: Vec<(&'static str, Regex)> = vec![
+        ("go", Regex::new(r"(?m)^package \w+\s*$").unwrap()),
+        ("rust", Regex::new(r"(?m)^(?:use (?:std|crate)::|(?:pub )?fn \w+\(|impl\b)").unwrap()),
+        ("python", Regex::new(r"(?m)^(?:def \w+\(.*\):|from [\w.]+ import |import \w+\s*$)").unwrap()),
+        ("lua", Regex::new(r"(?m)^local (?:function )?\w+").unwrap()),
+        ("javascript", Regex::new(r"(?m)^(?:const \w+ = require\(|import .* from '|export (?:default|const|function) )").unwrap()),
+    ];
+}
+
+/// Number of lines at the start or end of a file Vim looks for modelines in.
+const MODELINE_LINES: usize = 5;
+
+/// Interpreter named by a `#!` line, without directory or version suffix.
+pub fn shebang(content: &str) -> Option<&str> {
+    let line = content.strip_prefix("#!")?.lines().next()?;
+    let mut args = line.split_whitespace();
+    let mut program = basename(args.next()?);
+    if program == "env" {
+        program = basename(args.find(|arg| !arg.starts_with('-') && !arg.contains('='))?);
+    }
+    Some(program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.'))
+}
+
+fn basename(path: &str) -> &str {
+    Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(path)
+}
+
+/// Language named by an Emacs or Vim modeline.
+pub fn modeline(content: &str) -> Option<&str> {
+    // Emacs looks at the first line, or the second after a `#!` line.
+    let mut lines = content.lines();
+    let first = lines.next().unwrap_or_default();
+    let emacs_line = if first.starts_with("#!") {
+        lines.next().unwrap_or_default()
+    } else {
+        first
+    };
+    if let Some(captures) = EMACS_MODELINE.captures(emacs_line) {
+        let vars = captures.get(1).unwrap().as_str();
+        let mode = if vars.contains(':') {
+            vars.split(';').find_map(|var| {
+                let (key, value) = var.split_once(':')?;
+                key.trim()
+                    .eq_ignore_ascii_case("mode")
+                    .then(|| value.trim())
+            })
+        } else {
+            Some(vars.trim())
+        };
+        if let Some(mode) = mode.filter(|mode| !mode.is_empty()) {
+            return Some(mode);
+        }
+    }
+    let lines: Vec<&str> = content.lines().collect();
+    let tail = lines
+        .len()
+        .saturating_sub(MODELINE_LINES)
+        .max(MODELINE_LINES);
+    lines
+        .iter()
+        .take(MODELINE_LINES)
+        .chain(lines.iter().skip(tail))
+        .find_map(|line| VIM_MODELINE.captures(line))
+        .map(|captures| captures.get(1).unwrap().as_str())
+}
+
+/// Whether a C header looks like it is really C++.
+pub fn is_cpp(content: &str) -> bool {
+    CPP_HEURISTIC.is_match(content)
+}
+
+/// Guess the language of a file with nothing else to go on.
+pub fn guess(content: &str) -> Option<&'static str> {
+    HEURISTICS
+        .iter()
+        .find(|(_, regex)| regex.is_match(content))
+        .map(|(name, _)| *name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shebangs() {
+        assert_eq!(shebang("#!/usr/bin/env python3\nprint()"), Some("python"));
+        assert_eq!(shebang("#!/usr/bin/env -S node --harmony\n"), Some("node"));
+        assert_eq!(shebang("#!/usr/local/bin/lua5.3"), Some("lua"));
+        assert_eq!(shebang("# not a shebang"), None);
+    }
+
+    #[test]
+    fn modelines() {
+        assert_eq!(
+            modeline("// -*- mode: c++; tab-width: 4 -*-\n"),
+            Some("c++")
+        );
+        assert_eq!(modeline("#!/bin/sh\n# -*- python -*-\n"), Some("python"));
+        assert_eq!(
+            modeline("a\nb\nc\nd\ne\nf\ng\n# vim: set ft=lua ts=2:\n"),
+            Some("lua")
+        );
+        assert_eq!(modeline("-- vi: filetype=rust\n"), Some("rust"));
+        assert_eq!(modeline("no modeline here\n"), None);
+    }
+
+    #[test]
+    fn heuristics() {
+        assert!(is_cpp("#pragma once\nnamespace foo {\n}\n"));
+        assert!(!is_cpp("#pragma once\nstruct foo { int classes; };\n"));
+        assert_eq!(guess("package main\n\nfunc main() {}\n"), Some("go"));
+        assert_eq!(guess("import os\n\nos.exit(0)\n"), Some("python"));
+        assert_eq!(guess("Just some notes.\n"), None);
+    }
+}
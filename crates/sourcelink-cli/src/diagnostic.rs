@@ -0,0 +1,97 @@
+use crate::{error::SourcelinkError, parser::Location};
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem in a source file, pointing at where it occurred.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub file: String,
+    pub message: String,
+    pub location: Option<Location>,
+    /// Width of the offending text, in characters.
+    pub width: usize,
+}
+
+impl Diagnostic {
+    pub fn new(level: Level, file: &str, err: &anyhow::Error) -> Self {
+        let span = err
+            .downcast_ref::<SourcelinkError>()
+            .and_then(SourcelinkError::span);
+        Self {
+            level,
+            file: file.to_owned(),
+            message: err.to_string(),
+            location: span.map(|(location, _)| location),
+            width: span.map_or(1, |(_, width)| width),
+        }
+    }
+
+    /// Render the diagnostic with a snippet of the line it points at.
+    pub fn render(&self, content: &str) -> String {
+        let Some(location) = self.location else {
+            return format!("{}: {}: {}", self.level, self.file, self.message);
+        };
+        let line_start = content[..location.offset].rfind('\n').map_or(0, |i| i + 1);
+        let line = content[line_start..]
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches('\r');
+        // Keep tabs so the marker lines up with the source however they render.
+        let indent: String = content[line_start..location.offset]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let number = location.line.to_string();
+        let gutter = " ".repeat(number.len());
+        format!(
+            "{level}: {message}\n{gutter}--> {file}:{location}\n{gutter} |\n{number} | {line}\n{gutter} | {indent}{marker}",
+            level = self.level,
+            message = self.message,
+            file = self.file,
+            marker = "^".repeat(self.width.max(1)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::Construct;
+    use indoc::indoc;
+
+    #[test]
+    fn render() {
+        let content = "int main() {}\r\n\tx = 1; /* open\r\n";
+        let err = SourcelinkError::Unterminated {
+            construct: Construct::BlockComment,
+            delimiter: "/*".to_owned(),
+            location: Location::new(content, 23),
+        };
+        let diagnostic = Diagnostic::new(Level::Error, "main.c", &err.into());
+        assert_eq!(
+            diagnostic.render(content),
+            indoc! {"
+                error: Unterminated block comment `/*` opened at 2:9
+                 --> main.c:2:9
+                  |
+                2 | \tx = 1; /* open
+                  | \t       ^^"}
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{
+    diagnostic::{Diagnostic, Level},
+    parser::{Comment, Location},
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fmt;
+
+lazy_static! {
+    static ref DIRECTIVE_REGEX: Regex = Regex::new(r"sourcelink:([\w-]+)").unwrap();
+}
+
+/// What a directive in a comment asks for.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DirectiveKind {
+    /// Leave the links in this comment alone.
+    Ignore,
+    /// Leave the links on the next line alone.
+    IgnoreNextLine,
+    /// Leave links alone until `Enable`.
+    Disable,
+    Enable,
+    /// Leave the whole file alone.
+    IgnoreFile,
+}
+
+impl DirectiveKind {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ignore" => Some(Self::Ignore),
+            "ignore-next-line" => Some(Self::IgnoreNextLine),
+            "disable" => Some(Self::Disable),
+            "enable" => Some(Self::Enable),
+            "ignore-file" => Some(Self::IgnoreFile),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DirectiveKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Ignore => "ignore",
+            Self::IgnoreNextLine => "ignore-next-line",
+            Self::Disable => "disable",
+            Self::Enable => "enable",
+            Self::IgnoreFile => "ignore-file",
+        };
+        write!(f, "sourcelink:{name}")
+    }
+}
+
+#[derive(Debug)]
+struct Directive {
+    kind: DirectiveKind,
+    location: Location,
+    /// Index of the comment the directive is in.
+    comment: usize,
+    /// For `Disable`, where the region ends, and for `Enable`, whether it
+    /// ends one.
+    until: Option<usize>,
+    used: bool,
+}
+
+/// Directives found in the comments of a source, which decide what links are
+/// left alone.
+#[derive(Debug)]
+pub struct Directives {
+    directives: Vec<Directive>,
+    /// Names after `sourcelink:` that aren't directives.
+    unknown: Vec<(String, Location)>,
+}
+
+impl Directives {
+    pub fn new(content: &str, comments: &[Comment]) -> Self {
+        let mut directives = vec![];
+        let mut unknown = vec![];
+        for (i, comment) in comments
+            .iter()
+            .enumerate()
+            .filter(|(_, comment)| !comment.is_string())
+        {
+            for captures in DIRECTIVE_REGEX.captures_iter(comment.content()) {
+                let whole = captures.get(0).unwrap();
+                let location = Location::new(content, comment.start() + whole.start());
+                match DirectiveKind::from_name(&captures[1]) {
+                    Some(kind) => directives.push(Directive {
+                        kind,
+                        location,
+                        comment: i,
+                        until: None,
+                        used: false,
+                    }),
+                    None => unknown.push((whole.as_str().to_owned(), location)),
+                }
+            }
+        }
+        // Pair each disable with the enable after it.
+        let mut open: Vec<usize> = vec![];
+        for i in 0..directives.len() {
+            match directives[i].kind {
+                DirectiveKind::Disable => open.push(i),
+                DirectiveKind::Enable if !open.is_empty() => {
+                    let offset = directives[i].location.offset;
+                    for disable in open.drain(..) {
+                        directives[disable].until = Some(offset);
+                    }
+                    directives[i].until = Some(offset);
+                }
+                _ => {}
+            }
+        }
+        for disable in open {
+            directives[disable].until = Some(usize::MAX);
+        }
+        Self {
+            directives,
+            unknown,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.directives.is_empty() && self.unknown.is_empty()
+    }
+
+    /// Whether a link starting at `offset` on `line`, in the comment with
+    /// index `comment`, is to be left alone. Directives that say so are
+    /// marked as used.
+    pub fn suppresses(&mut self, comment: usize, offset: usize, line: usize) -> bool {
+        let mut suppressed = false;
+        for directive in &mut self.directives {
+            let applies = match directive.kind {
+                DirectiveKind::Ignore => directive.comment == comment,
+                DirectiveKind::IgnoreNextLine => directive.location.line + 1 == line,
+                DirectiveKind::Disable => directive
+                    .until
+                    .is_some_and(|until| (directive.location.offset..until).contains(&offset)),
+                DirectiveKind::Enable => false,
+                DirectiveKind::IgnoreFile => true,
+            };
+            if applies {
+                directive.used = true;
+                suppressed = true;
+            }
+        }
+        suppressed
+    }
+
+    /// Warnings about directives that didn't suppress anything, or aren't
+    /// directives at all.
+    pub fn diagnostics(&self, filename: &str) -> Vec<Diagnostic> {
+        let warning = |message: String, location: Location, width: usize| Diagnostic {
+            level: Level::Warning,
+            file: filename.to_owned(),
+            message,
+            location: Some(location),
+            width,
+        };
+        let mut diagnostics: Vec<Diagnostic> = self
+            .directives
+            .iter()
+            .filter_map(|directive| {
+                let kind = directive.kind;
+                let message = match kind {
+                    DirectiveKind::Enable if directive.until.is_none() => {
+                        format!("`{kind}` without a `sourcelink:disable` before it")
+                    }
+                    DirectiveKind::Enable => return None,
+                    _ if !directive.used => {
+                        format!("Unused `{kind}`, no links are left alone by it")
+                    }
+                    _ => return None,
+                };
+                let width = kind.to_string().len();
+                Some(warning(message, directive.location, width))
+            })
+            .collect();
+        diagnostics.extend(self.unknown.iter().map(|(text, location)| {
+            warning(format!("Unknown directive `{text}`"), *location, text.len())
+        }));
+        diagnostics.sort_by_key(|diagnostic| diagnostic.location.map(|location| location.offset));
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{lang::Lang, links::find_comment_links, parser::LineIndex};
+    use indoc::indoc;
+
+    fn kept(content: &str) -> (Vec<String>, Vec<String>) {
+        let comments = Lang::Python.parse(content).unwrap();
+        let index = LineIndex::new(content);
+        let mut directives = Directives::new(content, &comments);
+        let mut urls = vec![];
+        for i in 0..comments.len() {
+            for link in find_comment_links(content, &comments, i, None) {
+                let (line, _) = index.line_col(link.start());
+                if !directives.suppresses(i, link.start(), line) {
+                    urls.push(link.url().to_owned());
+                }
+            }
+        }
+        let messages = directives
+            .diagnostics("main.py")
+            .iter()
+            .map(|diagnostic| format!("{}: {}", diagnostic.location.unwrap(), diagnostic.message))
+            .collect();
+        (urls, messages)
+    }
+
+    #[test]
+    fn suppress() {
+        let (urls, messages) = kept(indoc! {"
+            # https://a.example sourcelink:ignore
+            # sourcelink:ignore-next-line
+            x = 1  # https://b.example
+            # https://c.example
+            # sourcelink:disable
+            # https://d.example
+            # sourcelink:enable
+            # https://e.example
+            # sourcelink:ignore sourcelink:enable sourcelink:ignroe
+        "});
+        assert_eq!(urls, vec!["https://c.example", "https://e.example"]);
+        assert_eq!(
+            messages,
+            vec![
+                "9:3: Unused `sourcelink:ignore`, no links are left alone by it",
+                "9:21: `sourcelink:enable` without a `sourcelink:disable` before it",
+                "9:39: Unknown directive `sourcelink:ignroe`",
+            ]
+        );
+
+        let (urls, messages) = kept("# https://a.example\n# sourcelink:ignore-file\n");
+        assert!(urls.is_empty());
+        assert!(messages.is_empty());
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{
+    lang::Lang,
+    links::Link,
+    parser::{continues_line_comment, line_comment_text, line_end, Comment, CommentView},
+};
+
+/// Documentation conventions a comment follows, which decide how a link put
+/// in place of another is written.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DocFormat {
+    Plain,
+    /// Rust doc comments, where bare URLs are written as `<url>` so rustdoc
+    /// links them.
+    Rustdoc,
+    /// JavaScript `/** */` comments, linking with `{@link url|text}`.
+    JsDoc,
+    /// Other `/** */` comments, where `@see` takes an `<a href>`.
+    Javadoc,
+}
+
+impl DocFormat {
+    /// Format of `comment`, a comment in `lang` found in `content`.
+    pub fn of(lang: &Lang, content: &str, comment: &Comment) -> Self {
+        if comment.is_string() {
+            return Self::Plain;
+        }
+        let text = comment.content();
+        let line = line_comment_text(content, comment).is_some();
+        // What follows the comment marker: `///` and `//!` for line comments,
+        // `/**` and `/*!` for block comments.
+        let doc = if line {
+            text.starts_with(['/', '!']) && !text.starts_with("//")
+        } else {
+            text.starts_with(['*', '!']) && !text.starts_with("**")
+        };
+        let block_doc = doc && !line && text.starts_with('*');
+        match lang {
+            Lang::Rust if doc => Self::Rustdoc,
+            Lang::JavaScript if block_doc => Self::JsDoc,
+            Lang::C | Lang::Cpp | Lang::Custom(_) if block_doc => Self::Javadoc,
+            _ => Self::Plain,
+        }
+    }
+
+    /// Text to put in place of `link`, found in `comments[i]`, for it to link
+    /// to `url` the way the comment's format does.
+    pub fn rewrite(
+        &self,
+        content: &str,
+        comments: &[Comment],
+        i: usize,
+        link: &Link,
+        url: String,
+    ) -> String {
+        let before = content[..link.start()].trim_end_matches([' ', '\t']);
+        match self {
+            Self::Rustdoc
+                if is_bare(content, link) && !in_code_block(content, comments, i, link) =>
+            {
+                format!("<{url}>")
+            }
+            Self::Javadoc if before.ends_with("@see") && before.len() < link.start() => {
+                format!("<a href=\"{url}\">{url}</a>")
+            }
+            _ => url,
+        }
+    }
+}
+
+/// Whether a link stands on its own in Markdown, rather than being the target
+/// of a link, an autolink, a reference definition or code.
+fn is_bare(content: &str, link: &Link) -> bool {
+    let before = &content[..link.start()];
+    let after = &content[link.end()..];
+    let quoted = before.ends_with(['<', '(', '[', '"', '\'', '`']);
+    let definition = before.trim_end().ends_with("]:");
+    // Backticks around the link may not be adjacent, as in `see <https://…>`.
+    let line = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+    let in_code_span = line.matches('`').count() % 2 == 1 && after.contains('`');
+    !quoted && !definition && !in_code_span
+}
+
+/// Whether a link is inside a fenced code block, such as a doc test.
+fn in_code_block(content: &str, comments: &[Comment], i: usize, link: &Link) -> bool {
+    let is_fence = |line: &str| {
+        let line = line.trim_start();
+        line.starts_with("```") || line.starts_with("~~~")
+    };
+    let mut fences = 0;
+    if line_comment_text(content, &comments[i]).is_some() {
+        let mut j = i;
+        while j > 0 && continues_line_comment(content, comments, j - 1) {
+            j -= 1;
+            let text_start = line_comment_text(content, &comments[j]).unwrap();
+            fences += usize::from(is_fence(
+                &content[text_start..line_end(content, text_start)],
+            ));
+        }
+    } else {
+        let view = CommentView::new(&comments[i]);
+        fences = view
+            .lines
+            .iter()
+            .take_while(|line| line.start + line.text.len() < link.start())
+            .filter(|line| is_fence(line.text))
+            .count();
+    }
+    fences % 2 == 1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::links::find_comment_links;
+
+    fn rewrite(lang: Lang, content: &str) -> Vec<String> {
+        let comments = lang.parse(content).unwrap();
+        let mut texts = vec![];
+        for (i, comment) in comments.iter().enumerate() {
+            let format = DocFormat::of(&lang, content, comment);
+            for link in find_comment_links(content, &comments, i, None) {
+                texts.push(format.rewrite(content, &comments, i, &link, "https://s/1".to_owned()));
+            }
+        }
+        texts
+    }
+
+    #[test]
+    fn formats() {
+        let content = "/// See https://a.example and [the spec](https://b.example).\n\
+            /// <https://c.example>\n\
+            ///\n\
+            /// [ref]: https://d.example\n\
+            /// ```\n\
+            /// // https://e.example\n\
+            /// ```\n\
+            // https://f.example\n\
+            fn f() {}\n";
+        assert_eq!(
+            rewrite(Lang::Rust, content),
+            vec![
+                "<https://s/1>",
+                "https://s/1",
+                "https://s/1",
+                "https://s/1",
+                "https://s/1",
+                "https://s/1",
+            ]
+        );
+
+        let content = "/**\n * {@link https://a.example/x|the docs}\n * https://b.example\n */\n";
+        assert_eq!(rewrite(Lang::JavaScript, content), vec!["https://s/1"; 2]);
+
+        let content =
+            "/**\n * @see https://a.example\n * @see <a href=\"https://b.example\">B</a>\n */\n";
+        assert_eq!(
+            rewrite(Lang::C, content),
+            vec!["<a href=\"https://s/1\">https://s/1</a>", "https://s/1"]
+        );
+    }
+}
@@ -1,14 +1,105 @@
+use crate::parser::Location;
+use std::fmt;
 use thiserror::Error;
 
-#[derive(Default, Error, PartialEq, Clone, Debug)]
+/// Syntax that can be left open at the end of a source.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Construct {
+    String,
+    BlockComment,
+}
+
+impl fmt::Display for Construct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String => write!(f, "string"),
+            Self::BlockComment => write!(f, "block comment"),
+        }
+    }
+}
+
+#[derive(Error, PartialEq, Clone, Debug)]
 pub enum SourcelinkError {
-    #[default]
-    #[error("Error parsing source")]
-    ParseError,
+    #[error("Unterminated {construct} `{delimiter}` opened at {location}")]
+    Unterminated {
+        construct: Construct,
+        delimiter: String,
+        location: Location,
+    },
+    #[error("String `{delimiter}` opened at {location} is not closed before the end of the line")]
+    UnclosedString {
+        delimiter: String,
+        location: Location,
+    },
+    #[error("Unexpected `{delimiter}` outside of a block comment at {location}")]
+    StrayDelimiter {
+        delimiter: String,
+        location: Location,
+    },
+    #[error("Syntax error at {location}")]
+    SyntaxError { location: Location },
     #[error("Unable to determine language of file {0}")]
     UnknownLanguage(String),
+    #[error("Unknown language {0}")]
+    UnknownLanguageName(String),
     #[error("Value {0} out of range {1}..{2}")]
     OutOfRange(usize, usize, usize),
-    #[error("Unexpectedly reached end of content")]
-    UnexpectedEOF,
+    #[error("Language {0} defines no comment syntax")]
+    InvalidLanguage(String),
+    #[error("Unable to parse {0} file(s)")]
+    ParseFailed(usize),
+    #[error("Unknown encoding {0}")]
+    UnknownEncoding(String),
+    #[error("Not valid {0}")]
+    Malformed(String),
+    #[error("Text cannot be represented in {0}")]
+    Unencodable(String),
+    #[error("Rewriting would change bytes {0} cannot round-trip")]
+    LossyEncoding(String),
+    #[error("No link server configured, add a [server] section to .sourcelink.toml")]
+    NoServer,
+    #[error("No API key, set api_key under [server] or SOURCELINK_API_KEY")]
+    NoApiKey,
+    #[error("Found {0} link(s) that should be shortened")]
+    Unshortened(usize),
+    #[error("git failed: {0}")]
+    Git(String),
+    #[error("Unable to rewrite {0} file(s)")]
+    RewriteFailed(usize),
+    #[error("{0} only applies when reading from stdin with `-`")]
+    StdinOnly(String),
+    #[error("`-` can't be combined with other paths, --staged or --interactive")]
+    StdinCombined,
+    #[error("No runs to undo")]
+    NoRuns,
+    #[error("No run {0} to undo")]
+    UnknownRun(String),
+    #[error("Unable to undo {0} file(s)")]
+    UndoFailed(usize),
+    #[error("Unable to delete {0} link(s)")]
+    DeleteFailed(usize),
+}
+
+impl SourcelinkError {
+    /// Location in the source the error points at, and the width of the
+    /// offending text in characters.
+    pub fn span(&self) -> Option<(Location, usize)> {
+        match self {
+            Self::Unterminated {
+                delimiter,
+                location,
+                ..
+            }
+            | Self::UnclosedString {
+                delimiter,
+                location,
+            }
+            | Self::StrayDelimiter {
+                delimiter,
+                location,
+            } => Some((*location, delimiter.chars().count())),
+            Self::SyntaxError { location } => Some((*location, 1)),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,337 @@
+use crate::error::SourcelinkError;
+use anyhow::{Context, Result};
+use std::{
+    io::{BufRead, BufReader, Write},
+    ops::Range,
+    path::PathBuf,
+    process::{Command, Stdio},
+    thread,
+};
+
+/// Run git in the current directory, returning what it printed.
+fn git(args: &[&str]) -> Result<Vec<u8>> {
+    git_with_input(args, &[])
+}
+
+fn git_with_input(args: &[&str], input: &[u8]) -> Result<Vec<u8>> {
+    let mut child = Command::new("git")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Unable to run git")?;
+    child.stdin.take().unwrap().write_all(input)?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(SourcelinkError::Git(stderr.trim().to_owned()).into());
+    }
+    Ok(output.stdout)
+}
+
+/// What to compare against to find changes.
+#[derive(Clone, Copy, Debug)]
+pub enum DiffBase<'a> {
+    /// Changes staged in the index, compared to `HEAD`.
+    Index,
+    /// Changes in the working tree since a revision.
+    Rev(&'a str),
+}
+
+/// A file with added or modified lines.
+#[derive(PartialEq, Debug)]
+pub struct Change {
+    /// Path relative to the current directory.
+    pub path: PathBuf,
+    /// 1-based line ranges in the new version of the file.
+    pub lines: Vec<Range<usize>>,
+}
+
+/// Files under the current directory changed relative to `base`. Deleted
+/// files and files with only deletions are left out.
+pub fn changes(base: DiffBase) -> Result<Vec<Change>> {
+    let mut args = vec![
+        "-c",
+        "core.quotePath=false",
+        "diff",
+        "--no-color",
+        "--no-ext-diff",
+        "--no-renames",
+        "--relative",
+        "--unified=0",
+        "--diff-filter=d",
+        "--src-prefix=a/",
+        "--dst-prefix=b/",
+    ];
+    match base {
+        DiffBase::Index => args.push("--cached"),
+        DiffBase::Rev(rev) => args.push(rev),
+    }
+    args.push("--");
+    Ok(parse_diff(&String::from_utf8_lossy(&git(&args)?)))
+}
+
+/// Contents of a file as staged in the index.
+pub fn read_index(path: &str) -> Result<Vec<u8>> {
+    git(&["cat-file", "blob", &format!(":./{path}")])
+}
+
+/// A file in a tree object.
+#[derive(PartialEq, Debug)]
+pub struct TreeEntry {
+    /// Path relative to the current directory.
+    pub path: String,
+    pub id: String,
+}
+
+/// Files in the tree of `rev` under `paths`, leaving out symlinks and
+/// submodules.
+pub fn tree(rev: &str, paths: &[PathBuf]) -> Result<Vec<TreeEntry>> {
+    let paths: Vec<_> = paths.iter().map(|path| path.to_string_lossy()).collect();
+    let mut args = vec!["ls-tree", "-r", "-z", rev, "--"];
+    args.extend(paths.iter().map(|path| path.as_ref()));
+    Ok(parse_tree(&String::from_utf8_lossy(&git(&args)?)))
+}
+
+fn parse_tree(output: &str) -> Vec<TreeEntry> {
+    output
+        .split_terminator('\0')
+        .filter_map(|line| {
+            let (info, path) = line.split_once('\t')?;
+            let mut info = info.split(' ');
+            let (mode, kind, id) = (info.next()?, info.next()?, info.next()?);
+            (kind == "blob" && mode != "120000").then(|| TreeEntry {
+                path: path.to_owned(),
+                id: id.to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Contents of the blobs `ids`, read with a single `git cat-file`.
+pub fn read_blobs(ids: &[&str]) -> Result<Vec<Vec<u8>>> {
+    let mut child = Command::new("git")
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Unable to run git")?;
+    // Write from another thread so git never blocks on a full stdout pipe
+    // while we are still writing.
+    let mut stdin = child.stdin.take().unwrap();
+    let input: String = ids.iter().map(|id| format!("{id}\n")).collect();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let blobs = parse_batch(BufReader::new(child.stdout.take().unwrap()), ids.len());
+    writer.join().unwrap()?;
+    child.wait()?;
+    blobs
+}
+
+fn parse_batch(mut reader: impl BufRead, count: usize) -> Result<Vec<Vec<u8>>> {
+    let mut blobs = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let size = match header.split_whitespace().collect::<Vec<_>>()[..] {
+            [_, "blob", size] => size.parse()?,
+            _ => {
+                return Err(SourcelinkError::Git(format!("unexpected `{}`", header.trim())).into())
+            }
+        };
+        let mut blob = vec![0; size];
+        reader.read_exact(&mut blob)?;
+        // Each blob is followed by a newline.
+        reader.read_exact(&mut [0])?;
+        blobs.push(blob);
+    }
+    Ok(blobs)
+}
+
+/// Path of a hook, honouring `core.hooksPath`.
+pub fn hook_path(name: &str) -> Result<PathBuf> {
+    let path = git(&["rev-parse", "--git-path", &format!("hooks/{name}")])?;
+    Ok(String::from_utf8_lossy(&path).trim().into())
+}
+
+/// Replace a file's contents in the index, leaving the working tree alone.
+pub fn write_index(path: &str, bytes: &[u8]) -> Result<()> {
+    let entry = String::from_utf8_lossy(&git(&["ls-files", "--stage", "--", path])?).into_owned();
+    let mode = entry
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| SourcelinkError::Git(format!("{path} is not in the index")))?;
+    let id = git_with_input(&["hash-object", "-w", "--no-filters", "--stdin"], bytes)?;
+    let id = String::from_utf8_lossy(&id);
+    let cacheinfo = format!("{mode},{},{path}", id.trim());
+    git(&["update-index", "--cacheinfo", &cacheinfo])?;
+    Ok(())
+}
+
+fn parse_diff(diff: &str) -> Vec<Change> {
+    let mut changes: Vec<Change> = vec![];
+    let mut in_header = false;
+    for line in diff.lines() {
+        if line.starts_with("diff --git ") {
+            in_header = true;
+        } else if let Some(hunk) = line.strip_prefix("@@ ") {
+            in_header = false;
+            if let (Some(change), Some(lines)) = (changes.last_mut(), hunk_lines(hunk)) {
+                change.lines.push(lines);
+            }
+        } else if let Some(path) = line.strip_prefix("+++ ").filter(|_| in_header) {
+            if let Some(path) = diff_path(path).strip_prefix("b/") {
+                changes.push(Change {
+                    path: path.into(),
+                    lines: vec![],
+                });
+            }
+        }
+    }
+    changes.retain(|change| !change.lines.is_empty());
+    changes
+}
+
+/// A path as git prints it in a diff header, where it is followed by a tab if
+/// it has a space, and quoted with C-style escapes if it has characters such
+/// as `"`, `\` or control characters.
+fn diff_path(path: &str) -> String {
+    let path = path.strip_suffix('\t').unwrap_or(path);
+    let Some(quoted) = path
+        .strip_prefix('"')
+        .and_then(|path| path.strip_suffix('"'))
+    else {
+        return path.to_owned();
+    };
+    let mut bytes = vec![];
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('a') => bytes.push(0x07),
+            Some('b') => bytes.push(0x08),
+            Some('t') => bytes.push(b'\t'),
+            Some('n') => bytes.push(b'\n'),
+            Some('v') => bytes.push(0x0b),
+            Some('f') => bytes.push(0x0c),
+            Some('r') => bytes.push(b'\r'),
+            // Bytes of non-ASCII characters are escaped in octal.
+            Some(digit @ '0'..='3') => {
+                let octal: String = std::iter::once(digit)
+                    .chain(chars.by_ref().take(2))
+                    .collect();
+                bytes.extend(u8::from_str_radix(&octal, 8).ok());
+            }
+            Some(c) => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            None => {}
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Lines added by a hunk, given a header like `-1,2 +3,4 @@`.
+fn hunk_lines(header: &str) -> Option<Range<usize>> {
+    let new = header
+        .split_whitespace()
+        .find_map(|part| part.strip_prefix('+'))?;
+    let (start, count) = match new.split_once(',') {
+        Some((start, count)) => (start.parse().ok()?, count.parse().ok()?),
+        None => (new.parse().ok()?, 1),
+    };
+    (count > 0).then_some(start..start + count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn parse() {
+        let diff = indoc! {"
+            diff --git a/src/main.c b/src/main.c
+            index 1234567..89abcde 100644
+            --- a/src/main.c
+            +++ b/src/main.c
+            @@ -1 +1,2 @@
+            -// old
+            +// https://example.com
+            ++++ b/not a file
+            @@ -10,2 +11 @@ int main() {
+            -a
+            -b
+            +c
+            @@ -20,3 +20,0 @@
+            -gone
+            diff --git a/only-deletions.py b/only-deletions.py
+            --- a/only-deletions.py
+            +++ b/only-deletions.py
+            @@ -3 +2,0 @@
+            -# removed
+        "};
+        assert_eq!(
+            parse_diff(diff),
+            vec![Change {
+                path: "src/main.c".into(),
+                lines: vec![1..3, 11..12],
+            }]
+        );
+    }
+
+    #[test]
+    fn paths() {
+        let diff = indoc! {"
+            diff --git a/a b.c b/a b.c
+            --- a/a b.c
+            +++ b/a b.c\t
+            @@ -0,0 +1 @@
+            +// https://example.com
+            diff --git \"a/tab\\there.c\" \"b/tab\\there.c\"
+            +++ \"b/tab\\there.c\"
+            @@ -0,0 +1 @@
+            +// https://example.com
+            diff --git \"a/caf\\303\\251 \\\"x\\\".c\" \"b/caf\\303\\251 \\\"x\\\".c\"
+            +++ \"b/caf\\303\\251 \\\"x\\\".c\"
+            @@ -0,0 +1 @@
+            +// https://example.com
+        "};
+        let paths: Vec<_> = parse_diff(diff)
+            .into_iter()
+            .map(|change| change.path)
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("a b.c"),
+                PathBuf::from("tab\there.c"),
+                PathBuf::from("café \"x\".c"),
+            ]
+        );
+    }
+
+    #[test]
+    fn trees() {
+        let output = "100644 blob aaa\tsrc/main.c\x00120000 blob bbb\tlink.c\0\
+            160000 commit ccc\tvendor/lib\x00100755 blob ddd\tbin/run me\0";
+        assert_eq!(
+            parse_tree(output),
+            vec![
+                TreeEntry {
+                    path: "src/main.c".to_owned(),
+                    id: "aaa".to_owned()
+                },
+                TreeEntry {
+                    path: "bin/run me".to_owned(),
+                    id: "ddd".to_owned()
+                },
+            ]
+        );
+        let batch = "aaa blob 3\nab\n\nddd blob 0\n\n";
+        let blobs = parse_batch(batch.as_bytes(), 2).unwrap();
+        assert_eq!(blobs, vec![b"ab\n".to_vec(), vec![]]);
+        assert!(parse_batch("eee missing\n".as_bytes(), 1).is_err());
+    }
+}
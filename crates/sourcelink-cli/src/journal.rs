@@ -0,0 +1,350 @@
+use crate::{
+    cache::{find_git_dir, hash},
+    rewrite::Replacement,
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::Cell,
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const JOURNAL_DIR: &str = "sourcelink/journal";
+const EXTENSION: &str = "jsonl";
+
+/// A replacement made in a file, `offset` being where `after` starts in the
+/// rewritten text.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Edit {
+    pub offset: usize,
+    pub before: String,
+    pub after: String,
+}
+
+/// A file rewritten by a run.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FileEdits {
+    /// Path relative to the repository root.
+    pub path: String,
+    /// Hash of the file as written, to tell whether it changed since.
+    pub hash: String,
+    pub edits: Vec<Edit>,
+}
+
+/// A line of a journal.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+enum Entry {
+    Run { command: String },
+    File(FileEdits),
+    Link { id: String },
+}
+
+/// What a `shorten` or `expand` run did, read back from its journal.
+#[derive(Debug)]
+pub struct Run {
+    pub id: String,
+    pub command: String,
+    pub files: Vec<FileEdits>,
+    /// Ids of the links the run created.
+    pub links: Vec<String>,
+    path: PathBuf,
+}
+
+/// Record of a run being made, written as it goes under
+/// `.git/sourcelink/journal/` so that even an interrupted run can be undone.
+pub struct Journal {
+    path: PathBuf,
+    root: PathBuf,
+    command: String,
+    /// Whether the file has been created, which waits for there to be
+    /// something to record.
+    started: Cell<bool>,
+}
+
+/// `YYYYMMDD-HHMMSS` in UTC for a Unix time.
+fn timestamp(secs: u64) -> String {
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+    // Howard Hinnant's `civil_from_days`.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Journal directory and work tree root of the repository containing the
+/// current directory.
+fn journal_dir() -> Result<Option<(PathBuf, PathBuf)>> {
+    let cwd = env::current_dir()?;
+    Ok(find_git_dir(&cwd).map(|(git_dir, root)| {
+        let root = root.canonicalize().unwrap_or(root);
+        (git_dir.join(JOURNAL_DIR), root)
+    }))
+}
+
+impl Journal {
+    /// Start recording a run of `command`, or `None` outside a repository.
+    pub fn start(command: &str) -> Result<Option<Self>> {
+        let Some((dir, root)) = journal_dir()? else {
+            log::warn!("Not in a git repository, so this run can't be undone");
+            return Ok(None);
+        };
+        Ok(Some(Self::open(&dir, &root, command)))
+    }
+
+    pub fn open(dir: &Path, root: &Path, command: &str) -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let id = timestamp(secs);
+        let mut path = dir.join(format!("{id}.{EXTENSION}"));
+        for n in 2.. {
+            if !path.exists() {
+                break;
+            }
+            path = dir.join(format!("{id}-{n}.{EXTENSION}"));
+        }
+        Self {
+            path,
+            root: root.to_owned(),
+            command: command.to_owned(),
+            started: Cell::new(false),
+        }
+    }
+
+    /// Id of the run, which `undo` takes.
+    pub fn id(&self) -> &str {
+        run_id(&self.path)
+    }
+
+    fn append(&self, entry: &Entry) -> Result<()> {
+        let mut lines = vec![];
+        if !self.started.get() {
+            fs::create_dir_all(self.path.parent().unwrap())?;
+            let run = Entry::Run {
+                command: self.command.clone(),
+            };
+            serde_json::to_writer(&mut lines, &run)?;
+            lines.push(b'\n');
+        }
+        serde_json::to_writer(&mut lines, entry)?;
+        lines.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(&lines)
+            .with_context(|| format!("Unable to write journal {}", self.path.display()))?;
+        self.started.set(true);
+        Ok(())
+    }
+
+    /// Record that `replacements` were made in `content`, giving the file at
+    /// `path` the contents `bytes`.
+    pub fn record_file(
+        &self,
+        path: &Path,
+        content: &str,
+        replacements: &[Replacement],
+        bytes: &[u8],
+    ) -> Result<()> {
+        let path = path.canonicalize()?;
+        let path = path
+            .strip_prefix(&self.root)
+            .with_context(|| format!("{} is outside the repository", path.display()))?;
+        let mut sorted: Vec<&Replacement> = replacements.iter().collect();
+        sorted.sort_by_key(|replacement| replacement.start);
+        let mut shift = 0isize;
+        let edits = sorted
+            .into_iter()
+            .map(|replacement| {
+                let offset = (replacement.start as isize + shift) as usize;
+                shift += replacement.text.len() as isize
+                    - (replacement.end - replacement.start) as isize;
+                Edit {
+                    offset,
+                    before: content[replacement.start..replacement.end].to_owned(),
+                    after: replacement.text.clone(),
+                }
+            })
+            .collect();
+        self.append(&Entry::File(FileEdits {
+            path: path.to_string_lossy().into_owned(),
+            hash: hash(bytes),
+            edits,
+        }))
+    }
+
+    pub fn record_link(&self, id: &str) -> Result<()> {
+        self.append(&Entry::Link { id: id.to_owned() })
+    }
+
+    /// Whether anything was recorded.
+    pub fn started(&self) -> bool {
+        self.started.get()
+    }
+}
+
+fn run_id(path: &Path) -> &str {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+}
+
+/// Sort key putting runs in the order they were made. Runs started in the
+/// same second as another get a `-n` suffix, which sorts before the first
+/// one's lack of a suffix as text.
+fn run_order(id: &str) -> (&str, usize) {
+    match id.rsplit_once('-') {
+        Some((first, n)) if first.contains('-') => (first, n.parse().unwrap_or_default()),
+        _ => (id, 1),
+    }
+}
+
+impl Run {
+    fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read journal {}", path.display()))?;
+        let mut run = Self {
+            id: run_id(path).to_owned(),
+            command: String::new(),
+            files: vec![],
+            links: vec![],
+            path: path.to_owned(),
+        };
+        for line in content.lines() {
+            // A run that was killed may have left half a line behind.
+            let Ok(entry) = serde_json::from_str(line) else {
+                continue;
+            };
+            match entry {
+                Entry::Run { command } => run.command = command,
+                Entry::File(file) => run.files.push(file),
+                Entry::Link { id } => run.links.push(id),
+            }
+        }
+        Ok(run)
+    }
+
+    /// Runs recorded for the repository containing the current directory,
+    /// oldest first, and the root of its work tree.
+    pub fn all() -> Result<(Vec<Self>, Option<PathBuf>)> {
+        let Some((dir, root)) = journal_dir()? else {
+            return Ok((vec![], None));
+        };
+        Ok((Self::all_in(&dir)?, Some(root)))
+    }
+
+    pub fn all_in(dir: &Path) -> Result<Vec<Self>> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Ok(vec![]);
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == EXTENSION))
+            .collect();
+        paths.sort_by(|a, b| run_order(run_id(a)).cmp(&run_order(run_id(b))));
+        paths.iter().map(|path| Self::load(path)).collect()
+    }
+
+    /// Write the run back with only the files and links still to undo,
+    /// removing it if there are none.
+    pub fn save_remaining(&self, files: Vec<FileEdits>, links: Vec<String>) -> Result<()> {
+        if files.is_empty() && links.is_empty() {
+            return Ok(fs::remove_file(&self.path)?);
+        }
+        let mut lines = vec![];
+        let run = Entry::Run {
+            command: self.command.clone(),
+        };
+        let entries = files
+            .into_iter()
+            .map(Entry::File)
+            .chain(links.into_iter().map(|id| Entry::Link { id }));
+        for entry in std::iter::once(run).chain(entries) {
+            serde_json::to_writer(&mut lines, &entry)?;
+            lines.push(b'\n');
+        }
+        Ok(fs::write(&self.path, lines)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(0), "19700101-000000");
+        assert_eq!(timestamp(1709251200 + 3723), "20240301-010203");
+        let mut ids = ["20240301-010203-2", "20240301-010204", "20240301-010203"];
+        ids.sort_by_key(|id| run_order(id));
+        assert_eq!(
+            ids,
+            ["20240301-010203", "20240301-010203-2", "20240301-010204"]
+        );
+    }
+
+    #[test]
+    fn record() {
+        let repo = tempfile::tempdir().unwrap();
+        let root = repo.path().canonicalize().unwrap();
+        let dir = root.join(".git").join(JOURNAL_DIR);
+        let file = root.join("main.c");
+        fs::write(&file, "// https://s/1 and https://s/22\n").unwrap();
+        let journal = Journal::open(&dir, &root, "shorten");
+        assert!(!journal.started());
+        let content = "// https://long/1 and https://long/22\n";
+        let replacements = [
+            Replacement {
+                start: 22,
+                end: 37,
+                text: "https://s/22".to_owned(),
+            },
+            Replacement {
+                start: 3,
+                end: 17,
+                text: "https://s/1".to_owned(),
+            },
+        ];
+        let bytes = fs::read(&file).unwrap();
+        journal
+            .record_file(&file, content, &replacements, &bytes)
+            .unwrap();
+        journal.record_link("1").unwrap();
+
+        let runs = Run::all_in(&dir).unwrap();
+        assert_eq!(runs.len(), 1);
+        let run = &runs[0];
+        assert_eq!(run.id, journal.id());
+        assert_eq!(run.command, "shorten");
+        assert_eq!(run.links, vec!["1"]);
+        assert_eq!(run.files[0].path, "main.c");
+        assert_eq!(run.files[0].hash, hash(&bytes));
+        assert_eq!(
+            run.files[0].edits[1],
+            Edit {
+                offset: 19,
+                before: "https://long/22".to_owned(),
+                after: "https://s/22".to_owned(),
+            }
+        );
+        run.save_remaining(vec![], vec![]).unwrap();
+        assert!(Run::all_in(&dir).unwrap().is_empty());
+    }
+}
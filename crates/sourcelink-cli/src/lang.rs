@@ -1,43 +1,286 @@
-use crate::{error::SourcelinkError, parser::*, parsers::*};
+use crate::{
+    config::Config,
+    detect::{self, Reason},
+    error::SourcelinkError,
+    parser::*,
+    parsers::*,
+    spec::LanguageSpec,
+};
 use anyhow::Result;
-use std::{ffi::OsStr, path::Path};
+use std::{ffi::OsStr, path::Path, sync::Arc};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Lang {
     C,
+    Cpp,
     Go,
     JavaScript,
     Lua,
     Python,
     Rust,
+    Custom(Arc<LanguageSpec>),
+}
+
+/// A detected language, and how it was detected.
+#[derive(Clone, Debug)]
+pub struct Detection {
+    pub lang: Lang,
+    pub reason: Reason,
+}
+
+fn extension(filename: &str) -> Result<&str> {
+    Path::new(filename)
+        .extension()
+        .and_then(OsStr::to_str)
+        .ok_or_else(|| SourcelinkError::UnknownLanguage(filename.to_owned()).into())
 }
 
 impl Lang {
+    const BUILTIN: [Lang; 7] = [
+        Self::C,
+        Self::Cpp,
+        Self::Go,
+        Self::JavaScript,
+        Self::Lua,
+        Self::Python,
+        Self::Rust,
+    ];
+
     pub fn from_filename(filename: &str) -> Result<Self> {
-        let ext = Path::new(filename)
-            .extension()
-            .and_then(OsStr::to_str)
-            .ok_or_else(|| SourcelinkError::UnknownLanguage(filename.to_owned()))?;
-        match ext {
-            "c" | "cpp" | "h" | "hpp" => Ok(Self::C),
-            "go" => Ok(Self::Go),
-            "js" | "jsx" | "ts" | "tsx" => Ok(Self::JavaScript),
-            "lua" => Ok(Self::Lua),
-            "py" => Ok(Self::Python),
-            "rs" => Ok(Self::Rust),
-            _ => Err(SourcelinkError::UnknownLanguage(filename.to_owned()).into()),
+        let ext = extension(filename)?;
+        Self::BUILTIN
+            .into_iter()
+            .find(|lang| lang.spec().matches_extension(ext))
+            .ok_or_else(|| SourcelinkError::UnknownLanguage(filename.to_owned()).into())
+    }
+
+    /// Find a language, preferring those defined in the config over the
+    /// built-in ones.
+    fn find(config: &Config, predicate: impl Fn(&LanguageSpec) -> bool) -> Option<Self> {
+        match config.languages.iter().find(|spec| predicate(spec)) {
+            Some(spec) => Some(Self::Custom(spec.clone())),
+            None => Self::BUILTIN
+                .into_iter()
+                .find(|lang| predicate(lang.spec())),
+        }
+    }
+
+    pub fn from_name(name: &str, config: &Config) -> Option<Self> {
+        Self::find(config, |spec| spec.matches_name(name))
+    }
+
+    /// Detect a language from the path alone: a config override, the
+    /// extension, or the exact file name.
+    pub fn detect_path(filename: &str, config: &Config) -> Option<Detection> {
+        let path = filename.strip_prefix("./").unwrap_or(filename);
+        let overridden = config
+            .overrides
+            .iter()
+            .find(|entry| entry.glob.is_match(path))
+            .and_then(|entry| Self::from_name(&entry.language, config));
+        if let Some(lang) = overridden {
+            return Some(Detection {
+                lang,
+                reason: Reason::Override,
+            });
+        }
+        if let Ok(ext) = extension(filename) {
+            if let Some(lang) = Self::find(config, |spec| spec.matches_extension(ext)) {
+                return Some(Detection {
+                    lang,
+                    reason: Reason::Extension,
+                });
+            }
+        }
+        let name = Path::new(filename).file_name()?.to_str()?;
+        Self::find(config, |spec| spec.matches_filename(name)).map(|lang| Detection {
+            lang,
+            reason: Reason::Filename,
+        })
+    }
+
+    /// Detect a language from the path, falling back to the `#!` line,
+    /// modelines and, for files without an extension, content heuristics.
+    pub fn detect(filename: &str, content: &str, config: &Config) -> Result<Detection> {
+        if let Some(detection) = Self::detect_path(filename, config) {
+            // `.h` is shared by C and C++.
+            let header = matches!(detection.reason, Reason::Extension)
+                && matches!(detection.lang, Self::C)
+                && extension(filename).is_ok_and(|ext| ext == "h");
+            if header && detect::is_cpp(content) {
+                return Ok(Detection {
+                    lang: Self::Cpp,
+                    reason: Reason::Heuristic,
+                });
+            }
+            return Ok(detection);
+        }
+        let candidates = [
+            (
+                Reason::Shebang,
+                detect::shebang(content).and_then(|interpreter| {
+                    Self::find(config, |spec| spec.matches_interpreter(interpreter))
+                }),
+            ),
+            (
+                Reason::Modeline,
+                detect::modeline(content).and_then(|mode| Self::from_name(mode, config)),
+            ),
+            (
+                Reason::Heuristic,
+                extension(filename)
+                    .is_err()
+                    .then(|| detect::guess(content))
+                    .flatten()
+                    .and_then(|name| Self::from_name(name, config)),
+            ),
+        ];
+        candidates
+            .into_iter()
+            .find_map(|(reason, lang)| lang.map(|lang| Detection { lang, reason }))
+            .ok_or_else(|| SourcelinkError::UnknownLanguage(filename.to_owned()).into())
+    }
+
+    pub fn spec(&self) -> &LanguageSpec {
+        match self {
+            Self::C => &C,
+            Self::Cpp => &CPP,
+            Self::Go => &GO,
+            Self::JavaScript => &JAVASCRIPT,
+            Self::Lua => &LUA,
+            Self::Python => &PYTHON,
+            Self::Rust => &RUST,
+            Self::Custom(spec) => spec,
         }
     }
 
+    pub fn name(&self) -> &str {
+        &self.spec().name
+    }
+
+    /// Tree-sitter grammar used to parse `filename` in this language, if there
+    /// is one.
+    #[cfg(feature = "tree-sitter")]
+    pub fn grammar(&self, filename: &str) -> Option<tree_sitter::Language> {
+        match self {
+            Self::C => Some(tree_sitter_c::LANGUAGE.into()),
+            Self::Cpp => Some(tree_sitter_cpp::LANGUAGE.into()),
+            Self::Go => Some(tree_sitter_go::LANGUAGE.into()),
+            // TypeScript's `<T>x` casts are JSX elements in TSX, so each
+            // variant needs its own grammar.
+            Self::JavaScript => match Path::new(filename).extension().and_then(OsStr::to_str) {
+                Some("ts") => Some(tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into()),
+                Some("tsx") => Some(tree_sitter_typescript::LANGUAGE_TSX.into()),
+                _ => Some(tree_sitter_javascript::LANGUAGE.into()),
+            },
+            Self::Python => Some(tree_sitter_python::LANGUAGE.into()),
+            Self::Rust => Some(tree_sitter_rust::LANGUAGE.into()),
+            Self::Lua | Self::Custom(_) => None,
+        }
+    }
+
+    pub fn parser<'source>(&self) -> Box<dyn Parser<'source> + '_> {
+        self.parser_for("", false)
+    }
+
+    /// Parser for `filename`, which also yields string literals if `strings`
+    /// is set.
+    #[cfg_attr(not(feature = "tree-sitter"), allow(unused_variables))]
+    pub fn parser_for<'source>(
+        &self,
+        filename: &str,
+        strings: bool,
+    ) -> Box<dyn Parser<'source> + '_> {
+        #[cfg(feature = "tree-sitter")]
+        if let Some(language) = self.grammar(filename) {
+            return Box::new(TreeSitterParser::new(self.spec(), language).with_strings(strings));
+        }
+        Box::new(GenericParser::new(self.spec()).with_strings(strings))
+    }
+
     pub fn parse<'source>(&self, content: &'source str) -> Result<Vec<Comment<'source>>> {
-        let parser: Box<dyn Parser> = match self {
-            Self::C => Box::new(CParser),
-            Self::Go | Self::JavaScript => Box::new(GoParser),
-            Self::Lua => Box::new(LuaParser),
-            Self::Python => Box::new(PythonParser),
-            Self::Rust => Box::new(RustParser),
-            _ => unimplemented!(),
-        };
-        parser.parse(content)
+        self.parser().parse(content)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use indoc::indoc;
+
+    fn detect(filename: &str, content: &str, config: &Config) -> Option<(String, Reason)> {
+        Lang::detect(filename, content, config)
+            .ok()
+            .map(|detection| (detection.lang.name().to_owned(), detection.reason))
+    }
+
+    #[test]
+    fn detect_custom() {
+        let config = Config::parse(
+            "[[languages]]\nname = \"sql\"\nextensions = [\"sql\", \"h\"]\nline_comments = [\"--\"]",
+        )
+        .unwrap();
+        assert!(matches!(
+            Lang::detect("a/b.sql", "", &config),
+            Ok(Detection {
+                lang: Lang::Custom(_),
+                ..
+            })
+        ));
+        assert!(matches!(
+            Lang::detect("a/b.h", "", &config),
+            Ok(Detection {
+                lang: Lang::Custom(_),
+                ..
+            })
+        ));
+        assert!(matches!(
+            Lang::detect("a/b.rs", "", &config),
+            Ok(Detection {
+                lang: Lang::Rust,
+                ..
+            })
+        ));
+        assert!(Lang::detect("a/b.txt", "", &config).is_err());
+    }
+
+    #[test]
+    fn detect_pipeline() {
+        let config = Config::parse(indoc! {r#"
+            [[overrides]]
+            pattern = "scripts/*.txt"
+            language = "lua"
+        "#})
+        .unwrap();
+        let python = Some(("python".to_owned(), Reason::Shebang));
+        assert_eq!(
+            detect("bin/deploy", "#!/usr/bin/env python3\n", &config),
+            python
+        );
+        assert_eq!(
+            detect("./scripts/a.txt", "", &config),
+            Some(("lua".to_owned(), Reason::Override))
+        );
+        assert_eq!(
+            detect("SConstruct", "", &config),
+            Some(("python".to_owned(), Reason::Filename))
+        );
+        assert_eq!(
+            detect("x.inc", "/* -*- mode: c++ -*- */\n", &config),
+            Some(("cpp".to_owned(), Reason::Modeline))
+        );
+        assert_eq!(
+            detect("a.h", "namespace a {}\n", &config),
+            Some(("cpp".to_owned(), Reason::Heuristic))
+        );
+        assert_eq!(
+            detect("a.h", "int a;\n", &config),
+            Some(("c".to_owned(), Reason::Extension))
+        );
+        assert_eq!(
+            detect("Gofile", "package main\n", &config),
+            Some(("go".to_owned(), Reason::Heuristic))
+        );
+        assert_eq!(detect("notes.txt", "package main\n", &config), None);
     }
 }
@@ -0,0 +1,27 @@
+//! Finding and rewriting links in source code comments.
+//!
+//! Used by the `sourcelink` CLI, and usable on its own to extract comments
+//! with [`lang::Lang`] and [`parser::Parser`].
+
+pub mod cache;
+pub mod client;
+pub mod commands;
+pub mod config;
+pub mod detect;
+pub mod diagnostic;
+pub mod directives;
+pub mod doc;
+pub mod error;
+pub mod git;
+pub mod journal;
+pub mod lang;
+pub mod links;
+pub mod normalize;
+pub mod parser;
+pub mod parsers;
+pub mod reflow;
+pub mod rewrite;
+pub mod rules;
+pub mod source;
+pub mod spec;
+pub mod walk;
@@ -0,0 +1,270 @@
+use crate::parser::{
+    continues_line_comment, line_comment_text, line_end, line_start, Comment, CommentView, SpanKind,
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::borrow::Cow;
+
+lazy_static! {
+    static ref URL_REGEX: Regex = Regex::new(r#"https?://[^\s<>"'`]+"#).unwrap();
+    /// The rest of a URL wrapped onto the next line, which has to be the
+    /// whole line, and not start with `#` as a heading or directive would.
+    static ref CONTINUATION_REGEX: Regex = Regex::new(r#"^[^\s<>"'`#][^\s<>"'`]*$"#).unwrap();
+}
+
+/// A URL found within a comment, with offsets into the whole source. A URL
+/// wrapped across lines spans the comment markers between its parts.
+#[derive(Clone, Debug)]
+pub struct Link<'source> {
+    url: Cow<'source, str>,
+    start: usize,
+    end: usize,
+}
+
+impl Link<'_> {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+}
+
+/// Trim trailing punctuation that is more likely prose than part of the URL.
+fn trim_url(url: &str) -> &str {
+    let mut url = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '*']);
+    for (open, close) in [('(', ')'), ('[', ']'), ('{', '}')] {
+        while url.ends_with(close) && url.matches(close).count() > url.matches(open).count() {
+            url = &url[..url.len() - 1];
+        }
+    }
+    url
+}
+
+/// The rest of a URL wrapped onto `text`, a line of a comment, if the line
+/// is nothing but a fragment of a URL path or query.
+fn continuation(text: &str) -> Option<&str> {
+    CONTINUATION_REGEX
+        .find(text.trim_end())
+        .map(|m| m.as_str())
+        .filter(|rest| !rest.starts_with("http") && rest.contains(['/', '?', '=', '&', '%']))
+}
+
+/// Whether the line ending at `end` reaches `width` columns, as a line a URL
+/// was wrapped from would.
+fn reaches(content: &str, end: usize, width: usize) -> bool {
+    content[line_start(content, end)..end].chars().count() >= width
+}
+
+/// `url` joined with the `rest` of it, trimmed, and how much of `rest` was
+/// trimmed off.
+fn join(url: &str, rest: &str) -> (String, usize) {
+    let joined = format!("{url}{rest}");
+    let trimmed = trim_url(&joined).len();
+    (joined[..trimmed].to_owned(), joined.len() - trimmed)
+}
+
+/// Links in a string literal, each ending before any escape, since what it
+/// escapes isn't read as written.
+fn find_string_links<'source>(string: &Comment<'source>, escape: char) -> Vec<Link<'source>> {
+    URL_REGEX
+        .find_iter(string.content())
+        .map(|m| {
+            let url = trim_url(m.as_str().split(escape).next().unwrap_or_default());
+            let start = string.start() + m.start();
+            Link {
+                url: Cow::Borrowed(url),
+                start,
+                end: start + url.len(),
+            }
+        })
+        .filter(|link| !link.url.ends_with("://"))
+        .collect()
+}
+
+/// Links in a comment, read through its [`CommentView`] so that a URL
+/// wrapped onto the next line of a block comment is joined with the rest of
+/// it, as [`find_comment_links`] does for line comments. Links in a string
+/// literal are read as they are.
+pub fn find_links<'source>(
+    content: &'source str,
+    comment: &Comment<'source>,
+    wrap_width: Option<usize>,
+) -> Vec<Link<'source>> {
+    if let SpanKind::String { escape, .. } = comment.kind() {
+        // Not a valid URL character, so a raw string's link runs as usual.
+        return find_string_links(comment, escape.unwrap_or(' '));
+    }
+    let view = CommentView::new(comment);
+    let mut links = vec![];
+    let mut consumed = 0;
+    for m in URL_REGEX.find_iter(&view.text) {
+        if m.start() < consumed {
+            continue;
+        }
+        let mut first = trim_url(m.as_str());
+        // JSDoc separates the text of a link with `|`, as in `{@link url|text}`.
+        let before = view.text[..m.start()].trim_end();
+        if ["{@link", "{@linkcode", "{@linkplain"]
+            .iter()
+            .any(|tag| before.ends_with(tag))
+        {
+            first = trim_url(first.split('|').next().unwrap_or_default());
+        }
+        let mut end = m.start() + first.len();
+        let mut joined: Option<String> = None;
+        while view.text[end..].starts_with('\n')
+            && wrap_width.is_some_and(|width| reaches(content, view.source_offset(end), width))
+        {
+            let next = view.text[end + 1..].lines().next().unwrap_or_default();
+            let Some(rest) = continuation(next) else {
+                break;
+            };
+            let (url, trimmed) = join(joined.as_deref().unwrap_or(first), rest);
+            end += 1 + rest.len() - trimmed;
+            joined = Some(url);
+        }
+        consumed = end;
+        let (start, end) = (view.source_offset(m.start()), view.source_offset(end));
+        let url = match joined {
+            Some(url) => Cow::Owned(url),
+            None => {
+                Cow::Borrowed(&comment.content()[start - comment.start()..end - comment.start()])
+            }
+        };
+        links.push(Link { url, start, end });
+    }
+    links
+}
+
+/// Links in `comments[i]`. With a `wrap_width`, a URL that runs to the end
+/// of a line comment reaching it is joined with its continuation on the next
+/// ones.
+pub fn find_comment_links<'source>(
+    content: &'source str,
+    comments: &[Comment<'source>],
+    i: usize,
+    wrap_width: Option<usize>,
+) -> Vec<Link<'source>> {
+    let mut links = find_links(content, &comments[i], wrap_width);
+    let (Some(last), Some(width)) = (links.last_mut(), wrap_width) else {
+        return links;
+    };
+    let mut j = i;
+    loop {
+        let text_end = comments[j].start() + comments[j].content().trim_end().len();
+        if last.end != text_end
+            || !reaches(content, text_end, width)
+            || !continues_line_comment(content, comments, j)
+        {
+            break;
+        }
+        let text_start = line_comment_text(content, &comments[j + 1]).unwrap();
+        let text = &content[text_start..line_end(content, text_start)];
+        let Some(rest) = continuation(text) else {
+            break;
+        };
+        let (url, trimmed) = join(&last.url, rest);
+        last.end = text_start + rest.len() - trimmed;
+        last.url = Cow::Owned(url);
+        j += 1;
+    }
+    links
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn find() {
+        let content = "see https://example.com/a_(b). and (https://example.com/c), <http://x.y>";
+        let comment = Comment::new(content, 10, 10 + content.len());
+        let links = find_links(content, &comment, None);
+        let urls: Vec<_> = links.iter().map(Link::url).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://example.com/a_(b)",
+                "https://example.com/c",
+                "http://x.y"
+            ]
+        );
+        assert_eq!(links[0].start(), 14);
+    }
+
+    #[test]
+    fn wrapped() {
+        let content = "// See https://example.com/a/very/long/pa\n// th/to/the/spec?x=1\n//   indented\n// https://example.com/docs\n// and more\nx(); // https://example.com/x\n// y_z\n";
+        let comments = crate::lang::Lang::C.parse(content).unwrap();
+        let links = find_comment_links(content, &comments, 0, Some(30));
+        assert_eq!(
+            links[0].url(),
+            "https://example.com/a/very/long/path/to/the/spec?x=1"
+        );
+        assert_eq!(
+            &content[links[0].start()..links[0].end()],
+            "https://example.com/a/very/long/pa\n// th/to/the/spec?x=1"
+        );
+        assert_eq!(
+            find_comment_links(content, &comments, 3, Some(30))[0].url(),
+            "https://example.com/docs"
+        );
+        assert_eq!(
+            find_comment_links(content, &comments, 5, Some(30))[0].url(),
+            "https://example.com/x"
+        );
+        assert_eq!(
+            find_comment_links(content, &comments, 0, None)[0].url(),
+            "https://example.com/a/very/long/pa"
+        );
+    }
+
+    #[test]
+    fn block_wrapped() {
+        let content = "/* see https://example.com/a/very/long/pa\n * th/to/the/spec */\n\
+            /* see https://example.com/a/very/long/pa\n * #define FOO */\n\
+            /* see https://example.com/a\n * b/c */\n";
+        let comments = crate::lang::Lang::C.parse(content).unwrap();
+        let urls: Vec<String> = comments
+            .iter()
+            .flat_map(|comment| find_links(content, comment, Some(30)))
+            .map(|link| link.url().to_owned())
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://example.com/a/very/long/path/to/the/spec",
+                "https://example.com/a/very/long/pa",
+                "https://example.com/a",
+            ]
+        );
+    }
+
+    #[test]
+    fn not_wrapped() {
+        let content = "// Docs: https://docs.rs/foo-bar-baz\n// my_function handles this\n\
+            // Docs: https://docs.rs/foo-bar-baz\n// my_function\n\
+            // Docs: https://docs.rs/foo-bar-baz\n// #define/FOO\n\
+            // https://a.example/b\n// c/d\n";
+        let comments = crate::lang::Lang::C.parse(content).unwrap();
+        let urls: Vec<String> = (0..comments.len())
+            .flat_map(|i| find_comment_links(content, &comments, i, Some(30)))
+            .map(|link| link.url().to_owned())
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://docs.rs/foo-bar-baz",
+                "https://docs.rs/foo-bar-baz",
+                "https://docs.rs/foo-bar-baz",
+                "https://a.example/b",
+            ]
+        );
+    }
+}
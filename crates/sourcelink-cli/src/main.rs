@@ -1,16 +1,100 @@
-mod error;
-mod lang;
-mod parser;
-mod parsers;
-
-use crate::lang::*;
 use anyhow::Result;
+use clap::{Parser, Subcommand};
+use sourcelink_cli::{
+    commands::{self, HookCommand, RewriteOptions, ScanOptions, ShortenOptions, UndoOptions},
+    config::Config,
+};
+use std::{io, path::PathBuf};
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    /// Path to the config file, instead of searching for `.sourcelink.toml`
+    #[arg(short, long, global = true)]
+    config: Option<PathBuf>,
+    /// Print more information about what is being done
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    #[command(subcommand)]
+    command: Command,
+}
 
-const EXAMPLE_C: &str = include_str!("../../../test/example.c");
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List links found in source comments
+    Scan {
+        /// Files or directories to scan
+        #[arg(default_value = ".")]
+        paths: Vec<PathBuf>,
+        #[command(flatten)]
+        options: ScanOptions,
+    },
+    /// List links that `shorten` would replace, failing if there are any
+    Check {
+        /// Files or directories to check
+        #[arg(default_value = ".")]
+        paths: Vec<PathBuf>,
+        #[command(flatten)]
+        options: ScanOptions,
+    },
+    /// Replace links in source comments with short links
+    Shorten {
+        /// Files or directories to rewrite
+        #[arg(default_value = ".")]
+        paths: Vec<PathBuf>,
+        #[command(flatten)]
+        options: ShortenOptions,
+    },
+    /// Replace short links in source comments with the links they point to
+    Expand {
+        /// Files or directories to rewrite
+        #[arg(default_value = ".")]
+        paths: Vec<PathBuf>,
+        #[command(flatten)]
+        options: RewriteOptions,
+    },
+    /// Show whether `shorten` would replace links, and which rule decides it
+    Explain {
+        /// Links to explain
+        #[arg(required = true)]
+        urls: Vec<String>,
+    },
+    /// Revert the files rewritten by a `shorten` or `expand` run
+    Undo {
+        #[command(flatten)]
+        options: UndoOptions,
+    },
+    /// Manage the git pre-commit hook
+    Hook {
+        #[command(subcommand)]
+        command: HookCommand,
+    },
+}
 
 fn main() -> Result<()> {
-    simple_logger::init().unwrap();
-    let comments = Lang::from_filename("example.c")?.parse(EXAMPLE_C)?;
-    log::info!("{:?}", comments);
-    Ok(())
+    let cli = Cli::parse();
+    let level = if cli.verbose {
+        log::Level::Debug
+    } else {
+        log::Level::Warn
+    };
+    simple_logger::init_with_level(level)?;
+    let config = Config::discover(cli.config.as_deref())?;
+    match &cli.command {
+        Command::Scan { paths, options } => commands::scan(&config, paths, options),
+        Command::Check { paths, options } => commands::check(&config, paths, options),
+        Command::Shorten { paths, options } => commands::shorten(
+            &config,
+            paths,
+            options,
+            &mut io::stdin().lock(),
+            &mut io::stdout(),
+        ),
+        Command::Expand { paths, options } => {
+            commands::expand(&config, paths, options, &mut io::stdin(), &mut io::stdout())
+        }
+        Command::Explain { urls } => commands::explain(&config, urls),
+        Command::Undo { options } => commands::undo(&config, options),
+        Command::Hook { command } => commands::hook(command),
+    }
 }
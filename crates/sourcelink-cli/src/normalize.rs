@@ -0,0 +1,133 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use url::Url;
+
+/// Query parameters stripped unless `strip_params` says otherwise.
+const TRACKING_PARAMS: &[&str] = &[
+    "utm_*", "fbclid", "gclid", "dclid", "msclkid", "mc_cid", "mc_eid",
+];
+
+/// How URLs are normalized before being shortened, so that links differing
+/// only in tracking parameters or spelling get the same short link. Parsing
+/// a URL also lowercases its host and drops a default port.
+#[derive(Deserialize, Debug)]
+#[serde(try_from = "NormalizeEntry")]
+pub struct NormalizeConfig {
+    pub enabled: bool,
+    /// Query parameters to remove, as globs.
+    pub strip_params: GlobSet,
+    /// Sort the remaining query parameters by name.
+    pub sort_query: bool,
+    /// Remove a trailing `/` from paths other than the root.
+    pub strip_trailing_slash: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct NormalizeEntry {
+    enabled: bool,
+    strip_params: Vec<String>,
+    sort_query: bool,
+    strip_trailing_slash: bool,
+}
+
+impl Default for NormalizeEntry {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            strip_params: TRACKING_PARAMS
+                .iter()
+                .map(|&param| param.to_owned())
+                .collect(),
+            sort_query: true,
+            strip_trailing_slash: false,
+        }
+    }
+}
+
+impl TryFrom<NormalizeEntry> for NormalizeConfig {
+    type Error = globset::Error;
+
+    fn try_from(entry: NormalizeEntry) -> Result<Self, Self::Error> {
+        let mut strip_params = GlobSetBuilder::new();
+        for param in &entry.strip_params {
+            strip_params.add(Glob::new(param)?);
+        }
+        Ok(Self {
+            enabled: entry.enabled,
+            strip_params: strip_params.build()?,
+            sort_query: entry.sort_query,
+            strip_trailing_slash: entry.strip_trailing_slash,
+        })
+    }
+}
+
+impl Default for NormalizeConfig {
+    fn default() -> Self {
+        NormalizeEntry::default().try_into().unwrap()
+    }
+}
+
+impl NormalizeConfig {
+    /// Normalized form of `url`, or `url` itself if it can't be parsed.
+    pub fn apply(&self, url: &str) -> String {
+        if !self.enabled {
+            return url.to_owned();
+        }
+        let Ok(mut parsed) = Url::parse(url) else {
+            return url.to_owned();
+        };
+        if let Some(query) = parsed.query() {
+            // Work on the raw query so values keep their encoding.
+            let mut params: Vec<&str> = query
+                .split('&')
+                .filter(|param| {
+                    let name = param.split('=').next().unwrap_or_default();
+                    !param.is_empty() && !self.strip_params.is_match(name)
+                })
+                .collect();
+            if self.sort_query {
+                params.sort_by_key(|param| param.split('=').next().unwrap_or_default());
+            }
+            let query = params.join("&");
+            parsed.set_query((!query.is_empty()).then_some(query.as_str()));
+        }
+        if self.strip_trailing_slash && parsed.path().len() > 1 && parsed.path().ends_with('/') {
+            let path = parsed.path().trim_end_matches('/').to_owned();
+            parsed.set_path(&path);
+        }
+        parsed.into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        let config = NormalizeConfig::default();
+        assert_eq!(
+            config.apply("HTTPS://Example.COM:443/a/?utm_source=x&b=2&fbclid=y&a=1%202#top"),
+            "https://example.com/a/?a=1%202&b=2#top"
+        );
+        assert_eq!(
+            config.apply("http://example.com:80/?utm_medium=email"),
+            "http://example.com/"
+        );
+
+        let config: NormalizeConfig = toml::from_str(
+            "strip_params = [\"session*\"]\nsort_query = false\nstrip_trailing_slash = true",
+        )
+        .unwrap();
+        assert_eq!(
+            config.apply("https://example.com/a/?utm_source=x&sessionid=1&b=2"),
+            "https://example.com/a?utm_source=x&b=2"
+        );
+        let config: NormalizeConfig = toml::from_str("enabled = false").unwrap();
+        assert_eq!(
+            config.apply("https://A.com/?utm_source=x"),
+            "https://A.com/?utm_source=x"
+        );
+    }
+}
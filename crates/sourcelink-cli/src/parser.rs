@@ -1,11 +1,27 @@
 use crate::error::*;
 use anyhow::Result;
+use std::fmt;
 
+/// What a span found by a parser is.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub enum SpanKind {
+    #[default]
+    Comment,
+    /// The inside of a string literal, only found when parsers are asked for
+    /// them. `quote` is the first character of its closing delimiter, and
+    /// `escape` the character escaping the one after it, if the string isn't
+    /// raw.
+    String { quote: char, escape: Option<char> },
+}
+
+/// A comment, or a string literal when those are asked for, with its
+/// delimiters left out.
 #[derive(Clone, Debug)]
 pub struct Comment<'source> {
     content: &'source str,
     start: usize,
     end: usize,
+    kind: SpanKind,
 }
 
 impl<'source> Comment<'source> {
@@ -14,11 +30,33 @@ impl<'source> Comment<'source> {
             content,
             start,
             end,
+            kind: SpanKind::Comment,
         }
     }
 
+    pub fn string(
+        content: &'source str,
+        start: usize,
+        end: usize,
+        quote: char,
+        escape: Option<char>,
+    ) -> Self {
+        Self {
+            kind: SpanKind::String { quote, escape },
+            ..Self::new(content, start, end)
+        }
+    }
+
+    pub fn kind(&self) -> SpanKind {
+        self.kind
+    }
+
+    pub fn is_string(&self) -> bool {
+        matches!(self.kind, SpanKind::String { .. })
+    }
+
     pub fn content(&self) -> &'source str {
-        &self.content
+        self.content
     }
 
     pub fn start(&self) -> usize {
@@ -30,14 +68,278 @@ impl<'source> Comment<'source> {
     }
 }
 
+/// A line of a comment with its decorations stripped.
+#[derive(Clone, Copy, Debug)]
+pub struct CommentLine<'source> {
+    /// The text, without trailing whitespace.
+    pub text: &'source str,
+    /// Offset of the text in the source.
+    pub start: usize,
+}
+
+/// A comment as it reads: repeated markers such as the third `/` of `///`,
+/// the ` * ` gutters of block comments and blank lines at either end are
+/// stripped, leaving its lines joined by `\n`. Offsets into `text` map back to
+/// the source with [`CommentView::source_offset`].
+#[derive(Debug)]
+pub struct CommentView<'source> {
+    pub text: String,
+    pub lines: Vec<CommentLine<'source>>,
+    /// Offset of each line in `text`.
+    starts: Vec<usize>,
+}
+
+impl<'source> CommentView<'source> {
+    pub fn new(comment: &Comment<'source>) -> Self {
+        let content = comment.content();
+        let mut lines = vec![];
+        let mut pos = 0;
+        for (i, raw) in content.split('\n').enumerate() {
+            let decoration = if i == 0 {
+                let markers = raw.len() - raw.trim_start_matches(MARKER_CHARS).len();
+                markers + usize::from(raw[markers..].starts_with(' '))
+            } else {
+                let indent = raw.len() - raw.trim_start_matches([' ', '\t']).len();
+                if raw[indent..].starts_with('*') {
+                    indent + 1 + usize::from(raw[indent + 1..].starts_with(' '))
+                } else {
+                    indent
+                }
+            };
+            lines.push(CommentLine {
+                text: raw[decoration..].trim_end(),
+                start: comment.start() + pos + decoration,
+            });
+            pos += raw.len() + 1;
+        }
+        let first = lines.iter().position(|line| !line.text.is_empty());
+        let last = lines.iter().rposition(|line| !line.text.is_empty());
+        let lines = match (first, last) {
+            (Some(first), Some(last)) => lines[first..=last].to_vec(),
+            _ => vec![],
+        };
+        let mut text = String::new();
+        let mut starts = vec![];
+        for line in &lines {
+            if !starts.is_empty() {
+                text.push('\n');
+            }
+            starts.push(text.len());
+            text.push_str(line.text);
+        }
+        Self {
+            text,
+            lines,
+            starts,
+        }
+    }
+
+    /// Offset in the source of an offset into `text`. The `\n` after a line
+    /// maps to the end of its text.
+    pub fn source_offset(&self, offset: usize) -> usize {
+        let i = self.starts.partition_point(|&start| start <= offset) - 1;
+        self.lines[i].start + (offset - self.starts[i])
+    }
+}
+
+/// Comments found in a source, along with the error that stopped parsing
+/// before the end of it, if any.
+#[derive(Default)]
+pub struct Parsed<'source> {
+    pub comments: Vec<Comment<'source>>,
+    pub error: Option<anyhow::Error>,
+    /// Unexpected input the parser recovered from, which usually means the
+    /// language spec is missing a token.
+    pub lexer_errors: Vec<SourcelinkError>,
+}
+
+impl<'source> Parsed<'source> {
+    pub fn into_result(self) -> Result<Vec<Comment<'source>>> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.comments),
+        }
+    }
+}
+
+/// Comments yielded one at a time, ending with an error if parsing stopped
+/// before the end of the source.
+pub type Comments<'a, 'source> = Box<dyn Iterator<Item = Result<Comment<'source>>> + 'a>;
+
 pub trait Parser<'source> {
-    fn parse(&self, content: &'source str) -> Result<Vec<Comment<'source>>>;
+    /// Parse as far as possible, keeping the comments found before an error.
+    fn parse_partial(&self, content: &'source str) -> Parsed<'source>;
+
+    /// Lazily yield the comments in `content`. Parsers that can't stream
+    /// parse the whole source up front.
+    fn comments<'a>(&'a self, content: &'source str) -> Comments<'a, 'source>
+    where
+        'source: 'a,
+    {
+        let parsed = self.parse_partial(content);
+        Box::new(
+            parsed
+                .comments
+                .into_iter()
+                .map(Ok)
+                .chain(parsed.error.map(Err)),
+        )
+    }
+
+    fn parse(&self, content: &'source str) -> Result<Vec<Comment<'source>>> {
+        self.comments(content).collect()
+    }
 }
 
-pub fn substr<'source>(s: &'source str, start: usize, end: usize) -> Result<&'source str> {
+pub fn substr(s: &str, start: usize, end: usize) -> Result<&str> {
     if end > s.len() {
         Err(SourcelinkError::OutOfRange(end, 0, s.len()).into())
     } else {
         Ok(&s[start..end])
     }
 }
+
+/// Characters that continue a line comment marker, as in `///`, `//!` or `##`.
+const MARKER_CHARS: &[char] = &['/', '!', '#', '*', '-', ';', '%'];
+
+/// Offset of the start of the line `offset` is on.
+pub fn line_start(s: &str, offset: usize) -> usize {
+    s[..offset].rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// Offset of the `\n` ending the line `offset` is on, or of the end of `s`.
+pub fn line_end(s: &str, offset: usize) -> usize {
+    s[offset..].find('\n').map_or(s.len(), |i| offset + i)
+}
+
+/// Start of the text of a comment that is alone on its line after a line
+/// comment marker, past any repeats of the marker and one space, or `None` if
+/// it isn't one.
+pub fn line_comment_text(content: &str, comment: &Comment) -> Option<usize> {
+    let rest = &content[comment.end()..];
+    let at_line_end = rest.is_empty() || rest.starts_with('\n') || rest.starts_with("\r\n");
+    let marker = content[line_start(content, comment.start())..comment.start()].trim_start();
+    if comment.is_string()
+        || comment.content().contains('\n')
+        || !at_line_end
+        || marker.is_empty()
+        || !marker.chars().all(|c| c.is_ascii_punctuation())
+    {
+        return None;
+    }
+    let text = comment.content();
+    let marker_len = text.len() - text.trim_start_matches(MARKER_CHARS).len();
+    let space = usize::from(text[marker_len..].starts_with(' '));
+    Some(comment.start() + marker_len + space)
+}
+
+/// Whether `comments[i]` and the comment after it are line comments on
+/// consecutive lines, with the same marker and indentation.
+pub fn continues_line_comment(content: &str, comments: &[Comment], i: usize) -> bool {
+    let Some(next) = comments.get(i + 1) else {
+        return false;
+    };
+    let prefix = |comment: &Comment| {
+        let text_start = line_comment_text(content, comment)?;
+        Some(content[line_start(content, text_start)..text_start].trim_end())
+    };
+    content[comments[i].end()..next.start()]
+        .matches('\n')
+        .count()
+        == 1
+        && prefix(&comments[i]).is_some()
+        && prefix(&comments[i]) == prefix(next)
+}
+
+/// 1-based line and column (in characters) of a byte offset.
+pub fn line_col(s: &str, offset: usize) -> (usize, usize) {
+    let before = &s[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let line = before.matches('\n').count() + 1;
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+/// Start offsets of the lines of a source, for looking up many offsets
+/// without rescanning the source each time.
+pub struct LineIndex<'source> {
+    content: &'source str,
+    starts: Vec<usize>,
+}
+
+impl<'source> LineIndex<'source> {
+    pub fn new(content: &'source str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { content, starts }
+    }
+
+    /// Same as [`line_col`].
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.starts.partition_point(|&start| start <= offset);
+        let line_start = self.starts[line - 1];
+        let column = self.content[line_start..offset].chars().count() + 1;
+        (line, column)
+    }
+}
+
+/// Position of a byte offset within a source.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Location {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    pub fn new(s: &str, offset: usize) -> Self {
+        let (line, column) = line_col(s, offset);
+        Self {
+            offset,
+            line,
+            column,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn line_index() {
+        let content = "a\n\nbé c\nd";
+        let index = LineIndex::new(content);
+        for offset in [0, 1, 2, 3, 6, 8, 9, content.len()] {
+            assert_eq!(
+                index.line_col(offset),
+                line_col(content, offset),
+                "{offset}"
+            );
+        }
+        assert_eq!(index.line_col(6), (3, 3));
+    }
+
+    #[test]
+    fn comment_view() {
+        let content = "/**\n   * See\n   *     code\n   *\n   * end  \n   */";
+        let comment = Comment::new(&content[2..content.len() - 2], 2, content.len() - 2);
+        let view = CommentView::new(&comment);
+        assert_eq!(view.text, "See\n    code\n\nend");
+        assert_eq!(view.source_offset(0), 9);
+        assert_eq!(&content[view.source_offset(4)..][..8], "    code");
+        assert_eq!(view.source_offset(3), 12);
+        let last = view.text.len() - 3;
+        assert_eq!(&content[view.source_offset(last)..], "end  \n   */");
+
+        let comment = Comment::new("/ Docs", 2, 8);
+        assert_eq!(CommentView::new(&comment).text, "Docs");
+    }
+}
@@ -1,105 +1,25 @@
-use crate::{error::*, parser::*};
-use anyhow::Result;
-use logos::Logos;
+use crate::spec::LanguageSpec;
+use lazy_static::lazy_static;
 
-#[derive(Logos, Clone, PartialEq, Debug)]
-#[logos(error = SourcelinkError)]
-enum Token {
-    #[token("\"")]
-    DoubleQuote,
-    #[token("'")]
-    SingleQuote,
-    #[token("//")]
-    DoubleSlash,
-    #[token("/*")]
-    SlashStar,
-    #[token("*/")]
-    StarSlash,
-    #[token("\n")]
-    NewLine,
-}
-
-#[derive(Debug)]
-enum ParseState {
-    Empty,
-    String(Token),
-    SingleLineComment(usize),
-    BlockComment(usize),
-}
-
-#[derive(Clone, Debug)]
-pub struct CParser;
-
-impl<'source> Parser<'source> for CParser {
-    fn parse(&self, content: &'source str) -> Result<Vec<Comment<'source>>> {
-        let mut comments = vec![];
-        let mut state = ParseState::Empty;
-        let mut lex = Token::lexer(content);
-        while let Some(result) = lex.next() {
-            if let Ok(current_token) = result {
-                state = match current_token {
-                    Token::DoubleQuote | Token::SingleQuote => match &state {
-                        ParseState::Empty => ParseState::String(current_token.clone()),
-                        ParseState::String(token) => {
-                            if current_token == *token {
-                                ParseState::Empty
-                            } else {
-                                state
-                            }
-                        }
-                        _ => state,
-                    },
-                    Token::DoubleSlash => {
-                        if matches!(state, ParseState::Empty) {
-                            ParseState::SingleLineComment(lex.span().end)
-                        } else {
-                            state
-                        }
-                    }
-                    Token::SlashStar => {
-                        if matches!(state, ParseState::Empty) {
-                            ParseState::BlockComment(lex.span().end)
-                        } else {
-                            state
-                        }
-                    }
-                    Token::StarSlash => {
-                        if let ParseState::BlockComment(start) = state {
-                            let end = lex.span().start;
-                            comments.push(Comment::new(substr(content, start, end)?, start, end));
-                            ParseState::Empty
-                        } else {
-                            state
-                        }
-                    }
-                    Token::NewLine => {
-                        if let ParseState::SingleLineComment(start) = state {
-                            let end = lex.span().start;
-                            comments.push(Comment::new(substr(content, start, end)?, start, end));
-                            ParseState::Empty
-                        } else {
-                            state
-                        }
-                    }
-                };
-            }
-        }
-        match state {
-            ParseState::Empty => Ok(comments),
-            _ => Err(SourcelinkError::ParseError.into()),
-        }
-    }
+lazy_static! {
+    pub static ref C: LanguageSpec = LanguageSpec::new("c")
+        .with_extensions(&["c", "h"])
+        .with_line_comment("//")
+        .with_block_comment("/*", "*/", false)
+        .with_string("\"", "\"", false)
+        .with_string("'", "'", false);
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{parser::*, parsers::GenericParser};
 
     const EXAMPLE_C: &str = include_str!("../../../../test/example.c");
 
     #[test]
     fn parse() {
-        let parser = CParser;
+        let parser = GenericParser::new(&C);
         let result = parser.parse(EXAMPLE_C);
         assert!(result.is_ok());
         let comments = result.unwrap();
@@ -0,0 +1,37 @@
+use crate::spec::LanguageSpec;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    pub static ref CPP: LanguageSpec = LanguageSpec::new("cpp")
+        .with_aliases(&["c++"])
+        .with_extensions(&["cpp", "cc", "cxx", "hpp", "hh", "hxx"])
+        .with_line_comment("//")
+        .with_block_comment("/*", "*/", false)
+        .with_string("\"", "\"", false)
+        .with_string("'", "'", false)
+        .with_raw_string("R\"(", ")\"", true);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parser::*, parsers::GenericParser};
+
+    const EXAMPLE_CPP: &str = include_str!("../../../../test/example.cpp");
+
+    #[test]
+    fn parse() {
+        let parser = GenericParser::new(&CPP);
+        let result = parser.parse(EXAMPLE_CPP);
+        assert!(result.is_ok());
+        let comments = result.unwrap();
+        assert_eq!(comments.len(), 4);
+        assert_eq!(
+            comments[0].content(),
+            " https://github.com/cschram/sourcelink\r"
+        );
+        assert_eq!(comments[1].content(), " lorem ipsum ");
+        assert_eq!(comments[2].content(), " https://www.google.com\r");
+        assert_eq!(comments[3].content(), "\r\n/* lorem ipsum ");
+    }
+}
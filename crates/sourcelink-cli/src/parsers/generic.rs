@@ -0,0 +1,517 @@
+use crate::{error::*, parser::*, spec::*};
+use anyhow::Result;
+
+enum Opener<'spec> {
+    LineComment(&'spec str),
+    BlockComment {
+        delimiter: &'spec BlockComment,
+        len: usize,
+        fences: usize,
+    },
+    String {
+        delimiter: &'spec StringDelimiter,
+        len: usize,
+        fences: usize,
+    },
+}
+
+impl<'spec> Opener<'spec> {
+    fn len(&self) -> usize {
+        match self {
+            Self::LineComment(marker) => marker.len(),
+            Self::BlockComment { len, .. } | Self::String { len, .. } => *len,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ParseState<'spec> {
+    Empty,
+    String {
+        delimiter: &'spec StringDelimiter,
+        start: usize,
+        /// Where the text after the opener starts.
+        text_start: usize,
+        fences: usize,
+    },
+    SingleLineComment(usize),
+    BlockComment {
+        delimiter: &'spec BlockComment,
+        start: usize,
+        nest_level: usize,
+        fences: usize,
+    },
+}
+
+/// Comment parser driven by a [`LanguageSpec`] rather than a token enum.
+#[derive(Clone, Debug)]
+pub struct GenericParser<'spec> {
+    spec: &'spec LanguageSpec,
+    /// Whether string literals are yielded along with comments.
+    strings: bool,
+}
+
+impl<'spec> GenericParser<'spec> {
+    pub fn new(spec: &'spec LanguageSpec) -> Self {
+        Self {
+            spec,
+            strings: false,
+        }
+    }
+
+    pub fn with_strings(mut self, strings: bool) -> Self {
+        self.strings = strings;
+        self
+    }
+
+    /// Find the longest comment or string opener at `pos` in `content`.
+    fn opener(&self, content: &str, pos: usize) -> Option<Opener<'spec>> {
+        let spec = self.spec;
+        let rest = &content[pos..];
+        let line_comments = spec
+            .line_comments
+            .iter()
+            .filter(|marker| rest.starts_with(marker.as_str()))
+            .map(|marker| Opener::LineComment(marker));
+        let block_comments = spec.block_comments.iter().filter_map(|delimiter| {
+            let (len, fences) = delimiter.opener(rest)?;
+            Some(Opener::BlockComment {
+                delimiter,
+                len,
+                fences,
+            })
+        });
+        let strings = spec.strings.iter().filter_map(|delimiter| {
+            let (len, fences) = delimiter.opener(rest)?;
+            if delimiter.regex && !expects_value(&content[..pos]) {
+                return None;
+            }
+            Some(Opener::String {
+                delimiter,
+                len,
+                fences,
+            })
+        });
+        line_comments
+            .chain(block_comments)
+            .chain(strings)
+            .filter(|opener| opener.len() > 0)
+            .reduce(|longest, opener| {
+                if opener.len() > longest.len() {
+                    opener
+                } else {
+                    longest
+                }
+            })
+    }
+}
+
+/// Keywords after which a value is expected, as in `return /x/`.
+const VALUE_KEYWORDS: [&str; 13] = [
+    "await",
+    "case",
+    "delete",
+    "do",
+    "else",
+    "in",
+    "instanceof",
+    "new",
+    "of",
+    "return",
+    "throw",
+    "typeof",
+    "yield",
+];
+
+/// Whether a value rather than an operator can follow `before`, so that a
+/// `/` there starts a regular expression rather than dividing.
+fn expects_value(before: &str) -> bool {
+    let word = |c: char| c == '_' || c == '$' || c.is_alphanumeric();
+    match before.chars().last() {
+        // `</` closes a JSX element.
+        Some('<') => false,
+        _ => match before.trim_end().chars().last() {
+            None => true,
+            Some(c) if word(c) => {
+                let last = before.trim_end().rsplit(|c: char| !word(c)).next();
+                last.is_some_and(|last| VALUE_KEYWORDS.contains(&last))
+            }
+            Some(c) => !matches!(c, ')' | ']' | '"' | '\'' | '`'),
+        },
+    }
+}
+
+fn char_len(rest: &str) -> usize {
+    rest.chars().next().map_or(1, char::len_utf8)
+}
+
+impl<'spec> GenericParser<'spec> {
+    /// Whether `rest` starts with a block comment closer that cannot be part
+    /// of another token, as in `int*/*comment*/`. Fenced closers such as
+    /// Lua's `]]` are left out, being ordinary code as in `a[b[1]]`.
+    fn stray_closer(&self, content: &str, pos: usize) -> Option<&'spec str> {
+        let rest = &content[pos..];
+        self.spec
+            .block_comments
+            .iter()
+            .filter(|delimiter| delimiter.fence.is_none())
+            .find(|delimiter| rest.starts_with(&delimiter.close))
+            .filter(|_| self.opener(content, pos + char_len(rest)).is_none())
+            .map(|delimiter| delimiter.close.as_str())
+    }
+
+    /// Lazily lex `content`, keeping track of the lexer errors met so far.
+    pub fn lex<'source>(&self, content: &'source str) -> GenericComments<'spec, 'source> {
+        GenericComments {
+            parser: self.clone(),
+            content,
+            state: ParseState::Empty,
+            pos: 0,
+            done: false,
+            lexer_errors: vec![],
+        }
+    }
+}
+
+/// Iterator over the comments of a source, returned by [`GenericParser::lex`].
+pub struct GenericComments<'spec, 'source> {
+    parser: GenericParser<'spec>,
+    content: &'source str,
+    state: ParseState<'spec>,
+    pos: usize,
+    done: bool,
+    lexer_errors: Vec<SourcelinkError>,
+}
+
+impl<'spec, 'source> GenericComments<'spec, 'source> {
+    /// Unexpected input recovered from so far.
+    pub fn lexer_errors(&self) -> &[SourcelinkError] {
+        &self.lexer_errors
+    }
+
+    fn comment(&self, start: usize, end: usize) -> Result<Comment<'source>> {
+        Ok(Comment::new(substr(self.content, start, end)?, start, end))
+    }
+
+    /// Advance by one token, returning a comment, or a string when those are
+    /// asked for, if one was closed.
+    fn step(&mut self) -> Option<Result<Comment<'source>>> {
+        let (content, pos) = (self.content, self.pos);
+        let rest = &content[pos..];
+        let mut comment = None;
+        let (next_state, advance) = match self.state {
+            ParseState::Empty => match self.parser.opener(content, pos) {
+                Some(opener) => {
+                    let end = pos + opener.len();
+                    let next_state = match opener {
+                        Opener::LineComment(_) => ParseState::SingleLineComment(end),
+                        Opener::BlockComment {
+                            delimiter, fences, ..
+                        } => ParseState::BlockComment {
+                            delimiter,
+                            start: end,
+                            nest_level: 0,
+                            fences,
+                        },
+                        Opener::String {
+                            delimiter, fences, ..
+                        } => ParseState::String {
+                            delimiter,
+                            start: pos,
+                            text_start: end,
+                            fences,
+                        },
+                    };
+                    (next_state, opener.len())
+                }
+                None => {
+                    if let Some(delimiter) = self.parser.stray_closer(content, pos) {
+                        self.lexer_errors.push(SourcelinkError::StrayDelimiter {
+                            delimiter: delimiter.to_owned(),
+                            location: Location::new(content, pos),
+                        });
+                    }
+                    (ParseState::Empty, char_len(rest))
+                }
+            },
+            state @ ParseState::String {
+                delimiter,
+                start,
+                text_start,
+                fences,
+            } => match delimiter.escape() {
+                Some(escape) if rest.starts_with(escape) => {
+                    let escaped = &rest[escape.len_utf8()..];
+                    let advance =
+                        escape.len_utf8() + escaped.chars().next().map_or(0, char::len_utf8);
+                    (state, advance)
+                }
+                _ if delimiter.closer(rest, fences).is_some() => {
+                    if self.parser.strings && !delimiter.regex {
+                        comment = Some(substr(content, text_start, pos).map(|text| {
+                            Comment::string(
+                                text,
+                                text_start,
+                                pos,
+                                delimiter.quote(),
+                                delimiter.escape(),
+                            )
+                        }));
+                    }
+                    (ParseState::Empty, delimiter.closer(rest, fences).unwrap())
+                }
+                // Recover at the end of the line, as compilers do.
+                _ if rest.starts_with('\n') && !delimiter.multiline => {
+                    self.lexer_errors.push(SourcelinkError::UnclosedString {
+                        delimiter: delimiter.open.clone(),
+                        location: Location::new(content, start),
+                    });
+                    (ParseState::Empty, 1)
+                }
+                _ => (state, char_len(rest)),
+            },
+            state @ ParseState::SingleLineComment(start) => {
+                if rest.starts_with('\n') {
+                    comment = Some(self.comment(start, pos));
+                    (ParseState::Empty, 1)
+                } else {
+                    (state, char_len(rest))
+                }
+            }
+            state @ ParseState::BlockComment {
+                delimiter,
+                start,
+                nest_level,
+                fences,
+            } => {
+                if let Some(len) = delimiter.closer(rest, fences) {
+                    if nest_level > 0 {
+                        let next_state = ParseState::BlockComment {
+                            delimiter,
+                            start,
+                            nest_level: nest_level - 1,
+                            fences,
+                        };
+                        (next_state, len)
+                    } else {
+                        comment = Some(self.comment(start, pos));
+                        (ParseState::Empty, len)
+                    }
+                } else if delimiter.nested && rest.starts_with(&delimiter.open) {
+                    let next_state = ParseState::BlockComment {
+                        delimiter,
+                        start,
+                        nest_level: nest_level + 1,
+                        fences,
+                    };
+                    (next_state, delimiter.open.len())
+                } else {
+                    (state, char_len(rest))
+                }
+            }
+        };
+        self.state = next_state;
+        self.pos += advance;
+        comment
+    }
+
+    /// Handle whatever is still open at the end of the source.
+    fn finish(&self) -> Option<Result<Comment<'source>>> {
+        let (construct, delimiter, opened_at) = match self.state {
+            ParseState::Empty => return None,
+            ParseState::SingleLineComment(start) => {
+                return Some(self.comment(start, self.content.len()));
+            }
+            ParseState::String {
+                delimiter, start, ..
+            } => (Construct::String, &delimiter.open, start),
+            ParseState::BlockComment {
+                delimiter,
+                start,
+                fences,
+                ..
+            } => (
+                Construct::BlockComment,
+                &delimiter.open,
+                start - delimiter.open.len() - fences * delimiter.fence.map_or(0, char::len_utf8),
+            ),
+        };
+        Some(Err(SourcelinkError::Unterminated {
+            construct,
+            delimiter: delimiter.clone(),
+            location: Location::new(self.content, opened_at),
+        }
+        .into()))
+    }
+}
+
+impl<'spec, 'source> Iterator for GenericComments<'spec, 'source> {
+    type Item = Result<Comment<'source>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        while self.pos < self.content.len() {
+            if let Some(comment) = self.step() {
+                self.done = comment.is_err();
+                return Some(comment);
+            }
+        }
+        self.done = true;
+        self.finish()
+    }
+}
+
+impl<'source, 'spec> Parser<'source> for GenericParser<'spec> {
+    fn parse_partial(&self, content: &'source str) -> Parsed<'source> {
+        let mut comments = self.lex(content);
+        let mut parsed = Parsed::default();
+        for comment in &mut comments {
+            match comment {
+                Ok(comment) => parsed.comments.push(comment),
+                Err(err) => parsed.error = Some(err),
+            }
+        }
+        parsed.lexer_errors = comments.lexer_errors;
+        parsed
+    }
+
+    fn comments<'a>(&'a self, content: &'source str) -> Comments<'a, 'source>
+    where
+        'source: 'a,
+    {
+        Box::new(self.lex(content))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use indoc::indoc;
+
+    const SPEC: &str = indoc! {r##"
+        name = "pascalish"
+        extensions = ["pas"]
+        line_comments = ["//"]
+        block_comments = [
+            { open = "(*", close = "*)", nested = true },
+            { open = "{", close = "}" },
+        ]
+        strings = [
+            { open = "'", close = "'", escape = "\\" },
+            { open = '"', close = '"', multiline = true },
+        ]
+    "##};
+
+    const EXAMPLE: &str = indoc! {r#"
+        // https://github.com/cschram/sourcelink
+        begin (* outer (* inner *) *)
+          writeln('it\'s { not a comment }'); { brace }
+        end. // trailing"#};
+
+    #[test]
+    fn parse() {
+        let spec: LanguageSpec = toml::from_str(SPEC).unwrap();
+        let parser = GenericParser::new(&spec);
+        let result = parser.parse(EXAMPLE);
+        assert!(result.is_ok());
+        let comments = result.unwrap();
+        assert_eq!(comments.len(), 4);
+        assert_eq!(
+            comments[0].content(),
+            " https://github.com/cschram/sourcelink"
+        );
+        assert_eq!(comments[1].content(), " outer (* inner *) ");
+        assert_eq!(comments[2].content(), " brace ");
+        assert_eq!(comments[3].content(), " trailing");
+    }
+
+    #[test]
+    fn unterminated() {
+        let spec: LanguageSpec = toml::from_str(SPEC).unwrap();
+        let parser = GenericParser::new(&spec);
+        let parsed = parser.parse_partial("{ a }\n  (* (* *)\n");
+        assert_eq!(parsed.comments.len(), 1);
+        let error = parsed.error.unwrap().downcast::<SourcelinkError>().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Unterminated block comment `(*` opened at 2:3"
+        );
+        assert_eq!(
+            error.span(),
+            Some((
+                Location {
+                    offset: 8,
+                    line: 2,
+                    column: 3
+                },
+                2
+            ))
+        );
+        assert!(parser.parse("'abc\\'").is_err());
+    }
+
+    #[test]
+    fn lazy() {
+        let spec: LanguageSpec = toml::from_str(SPEC).unwrap();
+        let parser = GenericParser::new(&spec);
+        let mut comments = parser.lex("{ a } 'b\n{ c } (* d");
+        assert_eq!(comments.next().unwrap().unwrap().content(), " a ");
+        assert!(comments.lexer_errors().is_empty());
+        assert_eq!(comments.next().unwrap().unwrap().content(), " c ");
+        assert_eq!(comments.lexer_errors().len(), 1);
+        assert!(comments.next().unwrap().is_err());
+        assert!(comments.next().is_none());
+        let contents: Vec<_> = parser
+            .comments(EXAMPLE)
+            .map(|comment| comment.unwrap().content())
+            .collect();
+        assert_eq!(contents.len(), 4);
+    }
+
+    #[test]
+    fn strings() {
+        let spec: LanguageSpec = toml::from_str(SPEC).unwrap();
+        let parser = GenericParser::new(&spec).with_strings(true);
+        let spans: Vec<_> = parser
+            .parse(EXAMPLE)
+            .unwrap()
+            .iter()
+            .map(|span| (span.kind(), span.content()))
+            .collect();
+        assert_eq!(spans.len(), 5);
+        assert_eq!(
+            spans[2],
+            (
+                SpanKind::String {
+                    quote: '\'',
+                    escape: Some('\\')
+                },
+                "it\\'s { not a comment }"
+            )
+        );
+        assert_eq!(spans[3], (SpanKind::Comment, " brace "));
+    }
+
+    #[test]
+    fn lexer_errors() {
+        let spec: LanguageSpec = toml::from_str(SPEC).unwrap();
+        let parser = GenericParser::new(&spec);
+        let parsed = parser.parse_partial("x := 'a\n*) \"b\nc\" (* d *)");
+        assert!(parsed.error.is_none());
+        assert_eq!(parsed.comments.len(), 1);
+        let errors: Vec<_> = parsed
+            .lexer_errors
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "String `'` opened at 1:6 is not closed before the end of the line",
+                "Unexpected `*)` outside of a block comment at 2:1",
+            ]
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use crate::spec::LanguageSpec;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    pub static ref JAVASCRIPT: LanguageSpec = LanguageSpec::new("javascript")
+        .with_aliases(&["js", "jsx", "typescript", "ts", "tsx"])
+        .with_extensions(&["js", "jsx", "mjs", "cjs", "ts", "tsx"])
+        .with_filenames(&["Jakefile"])
+        .with_interpreters(&["node", "nodejs", "deno", "bun"])
+        .with_line_comment("//")
+        .with_block_comment("/*", "*/", false)
+        .with_string("\"", "\"", false)
+        .with_string("'", "'", false)
+        .with_string("`", "`", true)
+        .with_regex_literal("/");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parser::*, parsers::GenericParser};
+
+    const EXAMPLE_JS: &str = include_str!("../../../../test/example.js");
+
+    #[test]
+    fn parse() {
+        let parser = GenericParser::new(&JAVASCRIPT);
+        let result = parser.parse(EXAMPLE_JS);
+        assert!(result.is_ok());
+        let comments = result.unwrap();
+        assert_eq!(comments.len(), 4);
+        assert_eq!(
+            comments[0].content(),
+            " https://github.com/cschram/sourcelink\r"
+        );
+        assert_eq!(comments[1].content(), " lorem ipsum ");
+        assert_eq!(comments[2].content(), " https://www.google.com\r");
+        assert_eq!(comments[3].content(), "\r\n/* lorem ipsum ");
+    }
+
+    #[test]
+    fn regex_literals() {
+        let content = "const re = /\"/;\n\
+            const q = a / b / c; // https://example.com/a\n\
+            if (/\\/\\/ not a comment/g.test(s)) return /'/;\n\
+            const el = <div>{x}</div>; // https://example.com/b\n";
+        let parsed = GenericParser::new(&JAVASCRIPT).parse_partial(content);
+        assert!(parsed.lexer_errors.is_empty(), "{:?}", parsed.lexer_errors);
+        let comments: Vec<_> = parsed.comments.iter().map(Comment::content).collect();
+        assert_eq!(
+            comments,
+            vec![" https://example.com/a", " https://example.com/b"]
+        );
+    }
+}
@@ -1,84 +1,27 @@
-use crate::{error::*, parser::*};
-use anyhow::Result;
-use logos::Logos;
+use crate::spec::LanguageSpec;
+use lazy_static::lazy_static;
 
-#[derive(Logos, Clone, PartialEq, Debug)]
-#[logos(error = SourcelinkError)]
-enum Token {
-    #[token("\"")]
-    DoubleQuote,
-    #[token("'")]
-    SingleQuote,
-    #[token("--")]
-    DoubleDash,
-    #[token("\n")]
-    NewLine,
-}
-
-#[derive(Debug)]
-enum ParseState {
-    Empty,
-    String(Token),
-    SingleLineComment(usize),
-}
-
-#[derive(Clone, Debug)]
-pub struct LuaParser;
-
-impl<'source> Parser<'source> for LuaParser {
-    fn parse(&self, content: &'source str) -> Result<Vec<Comment<'source>>> {
-        let mut comments = vec![];
-        let mut state = ParseState::Empty;
-        let mut lex = Token::lexer(content);
-        while let Some(result) = lex.next() {
-            if let Ok(current_token) = result {
-                state = match current_token {
-                    Token::DoubleQuote | Token::SingleQuote => match &state {
-                        ParseState::Empty => ParseState::String(current_token.clone()),
-                        ParseState::String(token) => {
-                            if current_token == *token {
-                                ParseState::Empty
-                            } else {
-                                state
-                            }
-                        }
-                        _ => state,
-                    },
-                    Token::DoubleDash => {
-                        if matches!(state, ParseState::Empty) {
-                            ParseState::SingleLineComment(lex.span().end)
-                        } else {
-                            state
-                        }
-                    }
-                    Token::NewLine => {
-                        if let ParseState::SingleLineComment(start) = state {
-                            let end = lex.span().start;
-                            comments.push(Comment::new(substr(content, start, end)?, start, end));
-                            ParseState::Empty
-                        } else {
-                            state
-                        }
-                    }
-                };
-            }
-        }
-        match state {
-            ParseState::Empty => Ok(comments),
-            _ => Err(SourcelinkError::ParseError.into()),
-        }
-    }
+lazy_static! {
+    pub static ref LUA: LanguageSpec = LanguageSpec::new("lua")
+        .with_extensions(&["lua"])
+        .with_interpreters(&["lua", "luajit"])
+        .with_line_comment("--")
+        .with_fenced_block_comment("--[[", "]]", '=')
+        .with_string("\"", "\"", false)
+        .with_string("'", "'", false)
+        .with_fenced_raw_string("[[", "]]", '=');
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{parser::*, parsers::GenericParser};
 
     const EXAMPLE_LUA: &str = include_str!("../../../../test/example.lua");
 
     #[test]
     fn parse() {
-        let parser = LuaParser;
+        let parser = GenericParser::new(&LUA);
         let result = parser.parse(EXAMPLE_LUA);
         assert!(result.is_ok());
         let comments = result.unwrap();
@@ -90,4 +33,20 @@ mod test {
         assert_eq!(comments[1].content(), " https://www.google.com\r");
         assert_eq!(comments[2].content(), " lorem -- ipsum\r");
     }
+
+    #[test]
+    fn long_brackets() {
+        let content = "x = [[a'b]] -- https://example.com/a\n\
+            --[==[ https://example.com/b ]] ]=] ]==]\n\
+            s = [=[ -- not a comment ]]\n]=]\n\
+            t = a[b[1]]\n";
+        let parsed = GenericParser::new(&LUA).parse_partial(content);
+        assert!(parsed.lexer_errors.is_empty());
+        assert!(parsed.error.is_none());
+        let comments: Vec<_> = parsed.comments.iter().map(Comment::content).collect();
+        assert_eq!(
+            comments,
+            vec![" https://example.com/a", " https://example.com/b ]] ]=] "]
+        );
+    }
 }
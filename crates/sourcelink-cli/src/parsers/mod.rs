@@ -1,11 +1,21 @@
 mod c;
+mod cpp;
+mod generic;
 mod go;
+mod javascript;
 mod lua;
 mod python;
 mod rust;
+#[cfg(feature = "tree-sitter")]
+mod treesitter;
 
-pub use self::c::CParser;
-pub use self::go::GoParser;
-pub use self::lua::LuaParser;
-pub use self::python::PythonParser;
-pub use self::rust::RustParser;
+pub use self::c::C;
+pub use self::cpp::CPP;
+pub use self::generic::{GenericComments, GenericParser};
+pub use self::go::GO;
+pub use self::javascript::JAVASCRIPT;
+pub use self::lua::LUA;
+pub use self::python::PYTHON;
+pub use self::rust::RUST;
+#[cfg(feature = "tree-sitter")]
+pub use self::treesitter::TreeSitterParser;
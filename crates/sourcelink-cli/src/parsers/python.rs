@@ -1,96 +1,29 @@
-use crate::{error::*, parser::*};
-use anyhow::Result;
-use logos::Logos;
+use crate::spec::LanguageSpec;
+use lazy_static::lazy_static;
 
-#[derive(Logos, Clone, PartialEq, Debug)]
-#[logos(error = SourcelinkError)]
-enum Token {
-    #[token("\"")]
-    DoubleQuote,
-    #[token("'")]
-    SingleQuote,
-    #[token("#")]
-    Hash,
-    #[token("\"\"\"")]
-    TripleQuote,
-    #[token("\n")]
-    NewLine,
-}
-
-#[derive(Debug)]
-enum ParseState {
-    Empty,
-    String(Token),
-    SingleLineComment(usize),
-    BlockComment(usize),
-}
-
-#[derive(Clone, Debug)]
-pub struct PythonParser;
-
-impl<'source> Parser<'source> for PythonParser {
-    fn parse(&self, content: &'source str) -> Result<Vec<Comment<'source>>> {
-        let mut comments = vec![];
-        let mut state = ParseState::Empty;
-        let mut lex = Token::lexer(content);
-        while let Some(result) = lex.next() {
-            if let Ok(current_token) = result {
-                state = match current_token {
-                    Token::DoubleQuote | Token::SingleQuote => match &state {
-                        ParseState::Empty => ParseState::String(current_token.clone()),
-                        ParseState::String(token) => {
-                            if current_token == *token {
-                                ParseState::Empty
-                            } else {
-                                state
-                            }
-                        }
-                        _ => state,
-                    },
-                    Token::Hash => {
-                        if matches!(state, ParseState::Empty) {
-                            ParseState::SingleLineComment(lex.span().end)
-                        } else {
-                            state
-                        }
-                    }
-                    Token::TripleQuote => match state {
-                        ParseState::Empty => ParseState::BlockComment(lex.span().end),
-                        ParseState::BlockComment(start) => {
-                            let end = lex.span().start;
-                            comments.push(Comment::new(substr(content, start, end)?, start, end));
-                            ParseState::Empty
-                        }
-                        _ => state,
-                    },
-                    Token::NewLine => {
-                        if let ParseState::SingleLineComment(start) = state {
-                            let end = lex.span().start;
-                            comments.push(Comment::new(substr(content, start, end)?, start, end));
-                            ParseState::Empty
-                        } else {
-                            state
-                        }
-                    }
-                };
-            }
-        }
-        match state {
-            ParseState::Empty => Ok(comments),
-            _ => Err(SourcelinkError::ParseError.into()),
-        }
-    }
+lazy_static! {
+    pub static ref PYTHON: LanguageSpec = LanguageSpec::new("python")
+        .with_aliases(&["py"])
+        .with_extensions(&["py", "pyw"])
+        .with_filenames(&["SConstruct", "SConscript"])
+        .with_interpreters(&["python", "pypy"])
+        .with_line_comment("#")
+        .with_block_comment("\"\"\"", "\"\"\"", false)
+        .with_block_comment("'''", "'''", false)
+        .with_string("\"", "\"", false)
+        .with_string("'", "'", false);
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{parser::*, parsers::GenericParser};
 
     const EXAMPLE_PY: &str = include_str!("../../../../test/example.py");
 
     #[test]
     fn parse() {
-        let parser = PythonParser;
+        let parser = GenericParser::new(&PYTHON);
         let result = parser.parse(EXAMPLE_PY);
         assert!(result.is_ok());
         let comments = result.unwrap();
@@ -105,4 +38,16 @@ mod test {
         );
         assert_eq!(comments[2].content(), " lorem ipsum\r");
     }
+
+    #[test]
+    fn single_quoted_docstrings() {
+        let content = "def f():\n    '''Don't use https://example.com/a'''\n    return 'x'  # https://example.com/b\n";
+        let parsed = GenericParser::new(&PYTHON).parse_partial(content);
+        assert!(parsed.lexer_errors.is_empty());
+        let comments: Vec<_> = parsed.comments.iter().map(Comment::content).collect();
+        assert_eq!(
+            comments,
+            vec!["Don't use https://example.com/a", " https://example.com/b"]
+        );
+    }
 }
@@ -1,153 +1,29 @@
-use crate::{error::*, parser::*};
-use anyhow::Result;
-use logos::Logos;
+use crate::spec::LanguageSpec;
+use lazy_static::lazy_static;
 
-#[derive(Logos, Clone, PartialEq, Debug)]
-#[logos(error = SourcelinkError)]
-enum Token {
-    #[token("\"")]
-    DoubleQuote,
-    #[token("'")]
-    SingleQuote,
-    #[token("r\"")]
-    RawOpenQuote,
-    #[token("r#\"")]
-    RawHashOpenQuote,
-    #[token("\"#")]
-    RawHashCloseQuote,
-    #[token("//")]
-    DoubleSlash,
-    #[token("/*")]
-    SlashStar,
-    #[token("*/")]
-    StarSlash,
-    #[token("\n")]
-    NewLine,
-}
-
-#[derive(Debug)]
-enum ParseState {
-    Empty,
-    String(Token),
-    SingleLineComment(usize),
-    BlockComment { start: usize, nest_level: usize },
-}
-
-#[derive(Clone, Debug)]
-pub struct RustParser;
-
-impl<'source> Parser<'source> for RustParser {
-    fn parse(&self, content: &'source str) -> Result<Vec<Comment<'source>>> {
-        let mut comments = vec![];
-        let mut state = ParseState::Empty;
-        let mut lex = Token::lexer(content);
-        while let Some(result) = lex.next() {
-            if let Ok(current_token) = result {
-                state = match current_token {
-                    Token::DoubleQuote | Token::SingleQuote => match &state {
-                        ParseState::Empty => ParseState::String(current_token.clone()),
-                        ParseState::String(token) => {
-                            if current_token == *token {
-                                ParseState::Empty
-                            } else {
-                                state
-                            }
-                        }
-                        _ => state,
-                    },
-                    Token::RawOpenQuote => {
-                        if matches!(state, ParseState::Empty) {
-                            ParseState::String(Token::DoubleQuote)
-                        } else {
-                            state
-                        }
-                    }
-                    Token::RawHashOpenQuote => {
-                        if matches!(state, ParseState::Empty) {
-                            ParseState::String(Token::RawHashCloseQuote)
-                        } else {
-                            state
-                        }
-                    }
-                    Token::RawHashCloseQuote => {
-                        if let ParseState::String(token) = &state {
-                            if current_token == *token {
-                                ParseState::Empty
-                            } else {
-                                state
-                            }
-                        } else {
-                            state
-                        }
-                    }
-                    Token::DoubleSlash => {
-                        if matches!(state, ParseState::Empty) {
-                            ParseState::SingleLineComment(lex.span().end)
-                        } else {
-                            state
-                        }
-                    }
-                    Token::SlashStar => match state {
-                        ParseState::Empty => ParseState::BlockComment {
-                            start: lex.span().end,
-                            nest_level: 0,
-                        },
-                        ParseState::BlockComment { start, nest_level } => {
-                            ParseState::BlockComment {
-                                start,
-                                nest_level: nest_level + 1,
-                            }
-                        }
-                        _ => state,
-                    },
-                    Token::StarSlash => {
-                        if let ParseState::BlockComment { start, nest_level } = state {
-                            if nest_level > 0 {
-                                ParseState::BlockComment {
-                                    start,
-                                    nest_level: nest_level - 1,
-                                }
-                            } else {
-                                let end = lex.span().start;
-                                comments.push(Comment::new(
-                                    substr(content, start, end)?,
-                                    start,
-                                    end,
-                                ));
-                                ParseState::Empty
-                            }
-                        } else {
-                            state
-                        }
-                    }
-                    Token::NewLine => {
-                        if let ParseState::SingleLineComment(start) = state {
-                            let end = lex.span().start;
-                            comments.push(Comment::new(substr(content, start, end)?, start, end));
-                            ParseState::Empty
-                        } else {
-                            state
-                        }
-                    }
-                };
-            }
-        }
-        match state {
-            ParseState::Empty => Ok(comments),
-            _ => Err(SourcelinkError::ParseError.into()),
-        }
-    }
+lazy_static! {
+    pub static ref RUST: LanguageSpec = LanguageSpec::new("rust")
+        .with_aliases(&["rs"])
+        .with_extensions(&["rs"])
+        .with_interpreters(&["rust-script"])
+        .with_line_comment("//")
+        .with_block_comment("/*", "*/", true)
+        .with_string("\"", "\"", true)
+        .with_char_literal("'")
+        .with_fenced_raw_string("r\"", "\"", '#');
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{parser::*, parsers::GenericParser};
 
     const EXAMPLE_RS: &str = include_str!("../../../../test/example.rs");
+    const LIFETIMES_RS: &str = include_str!("../../../../test/lifetimes.rs");
 
     #[test]
     fn parse() {
-        let parser = RustParser;
+        let parser = GenericParser::new(&RUST);
         let result = parser.parse(EXAMPLE_RS);
         assert!(result.is_ok());
         let comments = result.unwrap();
@@ -163,4 +39,26 @@ mod test {
             " /*\r\n/* lorem ipsum */ /* */\r\n*/ "
         );
     }
+
+    #[test]
+    fn lifetimes_and_raw_strings() {
+        let parser = GenericParser::new(&RUST);
+        let parsed = parser.parse_partial(LIFETIMES_RS);
+        assert!(parsed.lexer_errors.is_empty(), "{:?}", parsed.lexer_errors);
+        let contents: Vec<_> = parsed
+            .into_result()
+            .unwrap()
+            .iter()
+            .map(|comment| comment.content().trim_end())
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                " https://github.com/cschram/sourcelink",
+                " https://example.com/lifetimes",
+                " https://example.com/char",
+                " https://example.com/end",
+            ]
+        );
+    }
 }
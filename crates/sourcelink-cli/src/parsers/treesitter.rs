@@ -0,0 +1,300 @@
+use crate::{
+    error::SourcelinkError,
+    parser::*,
+    spec::{LanguageSpec, StringDelimiter},
+};
+use anyhow::{anyhow, Result};
+use tree_sitter::{Language, Node};
+
+/// Comment parser backed by a tree-sitter grammar.
+///
+/// Tree-sitter only tells us where a comment node is, so the spec is used to
+/// strip comment delimiters, giving the same `Comment` content as
+/// [`GenericParser`](super::GenericParser).
+pub struct TreeSitterParser<'spec> {
+    spec: &'spec LanguageSpec,
+    language: Language,
+    /// Whether string literals are collected along with comments.
+    strings: bool,
+}
+
+impl<'spec> TreeSitterParser<'spec> {
+    pub fn new(spec: &'spec LanguageSpec, language: Language) -> Self {
+        Self {
+            spec,
+            language,
+            strings: false,
+        }
+    }
+
+    pub fn with_strings(mut self, strings: bool) -> Self {
+        self.strings = strings;
+        self
+    }
+
+    /// Whether a node should be treated as a comment. Strings opened by a
+    /// block comment delimiter (Python docstrings) count as comments, as they
+    /// do for the generic parser.
+    fn is_comment(&self, node: &Node, content: &str) -> bool {
+        node.kind().ends_with("comment")
+            || (node.kind() == "string"
+                && self
+                    .spec
+                    .block_comments
+                    .iter()
+                    .any(|delimiter| content[node.byte_range()].starts_with(&delimiter.open)))
+    }
+
+    /// Byte range of a comment node's content, excluding its delimiters.
+    fn content_range(&self, node: &Node, content: &str) -> (usize, usize) {
+        let (start, end) = (node.start_byte(), node.end_byte());
+        let text = &content[start..end];
+        let block = self
+            .spec
+            .block_comments
+            .iter()
+            .filter(|delimiter| {
+                text.len() >= delimiter.open.len() + delimiter.close.len()
+                    && text.starts_with(&delimiter.open)
+                    && text.ends_with(&delimiter.close)
+            })
+            .max_by_key(|delimiter| delimiter.open.len());
+        if let Some(delimiter) = block {
+            return (start + delimiter.open.len(), end - delimiter.close.len());
+        }
+        let marker = self
+            .spec
+            .line_comments
+            .iter()
+            .filter(|marker| text.starts_with(marker.as_str()))
+            .max_by_key(|marker| marker.len());
+        match marker {
+            // Grammars disagree on whether a trailing `\r` or `\n` belongs to
+            // the comment, so run to the end of the line like the generic parser.
+            Some(marker) => {
+                let end = content[start..]
+                    .find('\n')
+                    .map_or(content.len(), |i| start + i);
+                (start + marker.len(), end)
+            }
+            None => (start, end),
+        }
+    }
+
+    /// Byte range of a string literal node's content and its delimiter, using
+    /// the spec's string delimiters. Prefixes such as `f` or `u8` that the
+    /// spec doesn't know of are skipped. `None` if no delimiter fits.
+    fn string_range(&self, node: &Node, content: &str) -> Option<(usize, usize, &StringDelimiter)> {
+        let (start, end) = (node.start_byte(), node.end_byte());
+        let text = &content[start..end];
+        let prefix = text.len()
+            - text
+                .trim_start_matches(|c: char| c.is_ascii_alphanumeric())
+                .len();
+        [0, prefix].into_iter().find_map(|skip| {
+            let text = &text[skip..];
+            self.spec
+                .strings
+                .iter()
+                .filter_map(|delimiter| {
+                    let (open, fences) = delimiter.opener(text)?;
+                    let close = delimiter.close_len(fences);
+                    let closed = text.len() >= open + close
+                        && delimiter.closer(&text[text.len() - close..], fences) == Some(close);
+                    closed.then_some((open, close, delimiter))
+                })
+                .max_by_key(|(open, ..)| *open)
+                .map(|(open, close, delimiter)| (start + skip + open, end - close, delimiter))
+        })
+    }
+
+    fn collect<'source>(
+        &self,
+        node: Node,
+        content: &'source str,
+        parsed: &mut Parsed<'source>,
+    ) -> Result<()> {
+        if node.is_error() || node.is_missing() {
+            parsed.lexer_errors.push(SourcelinkError::SyntaxError {
+                location: Location::new(content, node.start_byte()),
+            });
+        }
+        if self.is_comment(&node, content) {
+            let (start, end) = self.content_range(&node, content);
+            parsed
+                .comments
+                .push(Comment::new(substr(content, start, end)?, start, end));
+            return Ok(());
+        }
+        let string = matches!(
+            node.kind(),
+            "string"
+                | "string_literal"
+                | "raw_string_literal"
+                | "interpreted_string_literal"
+                | "template_string"
+        );
+        if self.strings && string {
+            if let Some((start, end, delimiter)) = self.string_range(&node, content) {
+                parsed.comments.push(Comment::string(
+                    substr(content, start, end)?,
+                    start,
+                    end,
+                    delimiter.quote(),
+                    delimiter.escape(),
+                ));
+                return Ok(());
+            }
+        }
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.collect(child, content, parsed)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'spec> TreeSitterParser<'spec> {
+    fn parse_into<'source>(
+        &self,
+        content: &'source str,
+        parsed: &mut Parsed<'source>,
+    ) -> Result<()> {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(&self.language)?;
+        let tree = parser
+            .parse(content, None)
+            .ok_or_else(|| anyhow!("tree-sitter failed to parse {}", self.spec.name))?;
+        self.collect(tree.root_node(), content, parsed)
+    }
+}
+
+impl<'source, 'spec> Parser<'source> for TreeSitterParser<'spec> {
+    fn parse_partial(&self, content: &'source str) -> Parsed<'source> {
+        let mut parsed = Parsed::default();
+        if let Err(err) = self.parse_into(content, &mut parsed) {
+            parsed.error = Some(err);
+        }
+        parsed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{lang::Lang, parsers::GenericParser};
+
+    const FIXTURES: [(&str, &str); 13] = [
+        ("example.c", include_str!("../../../../test/example.c")),
+        ("example.cpp", include_str!("../../../../test/example.cpp")),
+        ("example.h", include_str!("../../../../test/example.h")),
+        ("example.hpp", include_str!("../../../../test/example.hpp")),
+        ("example.go", include_str!("../../../../test/example.go")),
+        ("example.js", include_str!("../../../../test/example.js")),
+        ("example.jsx", include_str!("../../../../test/example.jsx")),
+        ("example.ts", include_str!("../../../../test/example.ts")),
+        ("example.tsx", include_str!("../../../../test/example.tsx")),
+        ("example.lua", include_str!("../../../../test/example.lua")),
+        ("example.py", include_str!("../../../../test/example.py")),
+        ("example.rs", include_str!("../../../../test/example.rs")),
+        (
+            "lifetimes.rs",
+            include_str!("../../../../test/lifetimes.rs"),
+        ),
+    ];
+
+    /// Comments only tree-sitter finds. `example.rs` contains
+    /// `'/* lorem ipsum */'`, which is not a valid char literal. Tree-sitter
+    /// recovers by reading a lifetime followed by a block comment.
+    const EXTRA: [(&str, &str); 1] = [("example.rs", " lorem ipsum ")];
+
+    fn spans<'source>(comments: &[Comment<'source>]) -> Vec<(usize, usize, &'source str)> {
+        comments
+            .iter()
+            .map(|comment| (comment.start(), comment.end(), comment.content()))
+            .collect()
+    }
+
+    #[test]
+    fn matches_generic_parser() {
+        for (filename, content) in FIXTURES {
+            let lang = Lang::from_filename(filename).unwrap();
+            let Some(language) = lang.grammar(filename) else {
+                continue;
+            };
+            let generic = GenericParser::new(lang.spec()).parse(content).unwrap();
+            let tree_sitter = TreeSitterParser::new(lang.spec(), language)
+                .parse(content)
+                .unwrap();
+            let (generic, tree_sitter) = (spans(&generic), spans(&tree_sitter));
+            let (extra, shared): (Vec<_>, Vec<_>) = tree_sitter
+                .into_iter()
+                .partition(|span| !generic.contains(span));
+            assert_eq!(generic, shared, "{filename}");
+            let expected: Vec<_> = EXTRA
+                .iter()
+                .filter(|(name, _)| *name == filename)
+                .map(|(_, content)| *content)
+                .collect();
+            let extra: Vec<_> = extra.iter().map(|(.., content)| *content).collect();
+            assert_eq!(extra, expected, "{filename}");
+        }
+    }
+
+    #[test]
+    fn strings() {
+        let content = "// a\nlet s = \"https://a.example/\\n\"; let r = r#\"b\"#; let c = 'c';\n";
+        let spans: Vec<_> =
+            TreeSitterParser::new(Lang::Rust.spec(), Lang::Rust.grammar("lib.rs").unwrap())
+                .with_strings(true)
+                .parse(content)
+                .unwrap()
+                .iter()
+                .map(|span| (span.kind(), span.content()))
+                .collect();
+        let generic: Vec<_> = GenericParser::new(Lang::Rust.spec())
+            .with_strings(true)
+            .parse(content)
+            .unwrap()
+            .iter()
+            .filter(|span| span.content() != "c")
+            .map(|span| (span.kind(), span.content()))
+            .collect();
+        assert_eq!(spans, generic);
+        assert_eq!(
+            spans[1],
+            (
+                SpanKind::String {
+                    quote: '"',
+                    escape: Some('\\')
+                },
+                "https://a.example/\\n"
+            )
+        );
+        assert_eq!(
+            spans[2],
+            (
+                SpanKind::String {
+                    quote: '"',
+                    escape: None
+                },
+                "b"
+            )
+        );
+    }
+
+    #[test]
+    fn grammar_by_extension() {
+        let content = "let x = <string>y; // https://a.example\n";
+        let parse = |filename| {
+            let language = Lang::JavaScript.grammar(filename).unwrap();
+            TreeSitterParser::new(Lang::JavaScript.spec(), language).parse_partial(content)
+        };
+        let parsed = parse("main.ts");
+        assert!(parsed.lexer_errors.is_empty());
+        assert_eq!(parsed.comments[0].content(), " https://a.example");
+        // As JSX, the cast opens an element that is never closed.
+        assert!(!parse("main.tsx").lexer_errors.is_empty());
+        assert!(!parse("main.js").lexer_errors.is_empty());
+    }
+}
@@ -0,0 +1,278 @@
+use crate::{
+    parser::{
+        continues_line_comment, line_comment_text, line_end, line_start, Comment, CommentView,
+    },
+    rewrite::Replacement,
+};
+use std::collections::BTreeMap;
+
+/// A line of a comment, split into the prefix before its text and the text.
+#[derive(Clone, Copy, Debug)]
+struct Line<'a> {
+    prefix: &'a str,
+    text_start: usize,
+    /// End of the text, without trailing whitespace.
+    text_end: usize,
+    /// Whether the line is left as it is: blank, indented, a list item, part
+    /// of a code block and so on.
+    verbatim: bool,
+}
+
+fn is_list_item(text: &str) -> bool {
+    if ["- ", "* ", "+ "]
+        .iter()
+        .any(|marker| text.starts_with(marker))
+    {
+        return true;
+    }
+    let digits = text.chars().take_while(char::is_ascii_digit).count();
+    digits > 0
+        && [". ", ") "]
+            .iter()
+            .any(|marker| text[digits..].starts_with(marker))
+}
+
+/// Mark the lines that aren't prose to be reflowed.
+fn mark_verbatim(content: &str, lines: &mut [Line]) {
+    let mut fenced = false;
+    for line in lines {
+        let end = line_end(content, line.text_start);
+        let text = content[line.text_start..end].trim_end();
+        let fence = text.starts_with("```") || text.starts_with("~~~");
+        line.verbatim = fenced
+            || fence
+            || text.is_empty()
+            || text.starts_with([' ', '\t'])
+            || text.starts_with(['#', '|', '>', '@'])
+            || is_list_item(text)
+            || !text.chars().any(char::is_alphanumeric);
+        fenced ^= fence;
+    }
+}
+
+/// Lines of the run of line comments around `comments[i]`, or of the block
+/// comment it is as its [`CommentView`] reads, leaving out the lines holding
+/// its delimiters.
+fn comment_lines<'a>(content: &'a str, comments: &[Comment], i: usize) -> Vec<Line<'a>> {
+    let line = |start: usize, text_start: usize| {
+        let end = line_end(content, text_start);
+        Line {
+            prefix: &content[start..text_start],
+            text_start,
+            text_end: text_start + content[text_start..end].trim_end().len(),
+            verbatim: false,
+        }
+    };
+    let mut lines = vec![];
+    if line_comment_text(content, &comments[i]).is_some() {
+        let mut first = i;
+        while first > 0 && continues_line_comment(content, comments, first - 1) {
+            first -= 1;
+        }
+        let mut last = i;
+        while continues_line_comment(content, comments, last) {
+            last += 1;
+        }
+        for comment in &comments[first..=last] {
+            let text_start = line_comment_text(content, comment).unwrap();
+            lines.push(line(line_start(content, text_start), text_start));
+        }
+    } else {
+        let comment = &comments[i];
+        let delimiter_lines = (
+            line_start(content, comment.start()),
+            line_start(content, comment.end()),
+        );
+        for view_line in CommentView::new(comment).lines {
+            let start = line_start(content, view_line.start);
+            if start != delimiter_lines.0 && start != delimiter_lines.1 {
+                lines.push(line(start, view_line.start));
+            }
+        }
+    }
+    mark_verbatim(content, &mut lines);
+    lines
+}
+
+/// Lines of the paragraph a replacement is in, or `None` if it isn't in one
+/// that can be reflowed.
+fn paragraph<'a>(
+    content: &'a str,
+    comments: &[Comment],
+    replacement: &Replacement,
+) -> Option<Vec<Line<'a>>> {
+    let i = comments.partition_point(|comment| comment.end() < replacement.start);
+    if comments.get(i)?.start() > replacement.start || comments[i].is_string() {
+        return None;
+    }
+    let lines = comment_lines(content, comments, i);
+    let on = |offset: usize| {
+        lines
+            .iter()
+            .position(|line| (line.text_start..=line.text_end).contains(&offset))
+    };
+    // A link wrapped across lines ends on a later one.
+    let (k, end) = (on(replacement.start)?, on(replacement.end)?);
+    if lines[k].verbatim {
+        return None;
+    }
+    let joins = |a: &Line, b: &Line| !b.verbatim && a.prefix == b.prefix;
+    let mut first = k;
+    while first > 0 && joins(&lines[first], &lines[first - 1]) {
+        first -= 1;
+    }
+    let mut last = k;
+    while last + 1 < lines.len() && joins(&lines[last], &lines[last + 1]) {
+        last += 1;
+    }
+    (end <= last).then(|| lines[first..=last].to_vec())
+}
+
+/// Words of `text` filled into lines of at most `width` characters, words
+/// too long for a line getting one of their own.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut current = String::new();
+    let mut current_width = 0;
+    for word in text.split_whitespace() {
+        let word_width = word.chars().count();
+        if !current.is_empty() && current_width + 1 + word_width > width {
+            lines.push(std::mem::take(&mut current));
+            current_width = 0;
+        }
+        if !current.is_empty() {
+            current.push(' ');
+            current_width += 1;
+        }
+        current.push_str(word);
+        current_width += word_width;
+    }
+    lines.push(current);
+    lines
+}
+
+/// Replacement of a paragraph by its text with `replacements` made, rewrapped
+/// to `width`.
+fn rewrap(
+    content: &str,
+    lines: &[Line],
+    replacements: &[&Replacement],
+    width: usize,
+) -> Replacement {
+    let mut text = String::new();
+    let mut pos = 0;
+    for line in lines {
+        pos = pos.max(line.text_start);
+        for replacement in replacements
+            .iter()
+            .filter(|replacement| (line.text_start..=line.text_end).contains(&replacement.start))
+        {
+            text.push_str(&content[pos..replacement.start]);
+            text.push_str(&replacement.text);
+            pos = replacement.end;
+        }
+        if pos <= line.text_end {
+            text.push_str(&content[pos..line.text_end]);
+        }
+        text.push(' ');
+    }
+    let (first, last) = (lines[0], lines[lines.len() - 1]);
+    let eol = if content[..line_end(content, first.text_start)].ends_with('\r') {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let prefix_width = first.prefix.chars().count();
+    let wrapped = wrap(&text, width.saturating_sub(prefix_width).max(1));
+    Replacement {
+        start: first.text_start,
+        end: last.text_end,
+        text: wrapped.join(&format!("{eol}{}", first.prefix)),
+    }
+}
+
+/// Turn `replacements` of links in `comments` into replacements of the
+/// paragraphs they are in, rewrapped to `width` columns. Links outside of
+/// paragraphs, such as in code blocks, list items or trailing comments, are
+/// replaced as they are.
+pub fn reflow(
+    content: &str,
+    comments: &[Comment],
+    replacements: &[Replacement],
+    width: usize,
+) -> Vec<Replacement> {
+    let mut edits = vec![];
+    let mut paragraphs: BTreeMap<usize, (Vec<Line>, Vec<&Replacement>)> = BTreeMap::new();
+    for replacement in replacements {
+        match paragraph(content, comments, replacement) {
+            Some(lines) => paragraphs
+                .entry(lines[0].text_start)
+                .or_insert_with(|| (lines, vec![]))
+                .1
+                .push(replacement),
+            None => edits.push(replacement.clone()),
+        }
+    }
+    for (lines, mut replacements) in paragraphs.into_values() {
+        replacements.sort_by_key(|replacement| replacement.start);
+        edits.push(rewrap(content, &lines, &replacements, width));
+    }
+    edits
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{lang::Lang, links::find_comment_links, rewrite::apply};
+    use indoc::indoc;
+
+    fn shorten(lang: Lang, content: &str, width: usize) -> String {
+        let comments = lang.parse(content).unwrap();
+        let replacements: Vec<Replacement> = (0..comments.len())
+            .flat_map(|i| find_comment_links(content, &comments, i, None))
+            .map(|link| Replacement {
+                start: link.start(),
+                end: link.end(),
+                text: "https://s/1".to_owned(),
+            })
+            .collect();
+        apply(content, &reflow(content, &comments, &replacements, width))
+    }
+
+    #[test]
+    fn line_comments() {
+        let content = indoc! {"
+            /// Parses the header as described
+            /// in https://example.com/a/very/long/path/to/the/spec/section
+            /// for the details.
+            ///
+            /// ```
+            /// // https://example.com/in/a/doc/test
+            /// ```
+            /// - https://example.com/in/a/list
+            fn parse() {} // https://example.com/trailing
+        "};
+        assert_eq!(
+            shorten(Lang::Rust, content, 40),
+            indoc! {"
+                /// Parses the header as described in
+                /// https://s/1 for the details.
+                ///
+                /// ```
+                /// // https://s/1
+                /// ```
+                /// - https://s/1
+                fn parse() {} // https://s/1
+            "}
+        );
+    }
+
+    #[test]
+    fn block_comments() {
+        let content = "/*\r\n * See https://example.com/x for\r\n * more.\r\n *\r\n *     https://example.com/code\r\n */\r\n";
+        assert_eq!(
+            shorten(Lang::C, content, 20),
+            "/*\r\n * See https://s/1\r\n * for more.\r\n *\r\n *     https://s/1\r\n */\r\n"
+        );
+    }
+}
@@ -0,0 +1,240 @@
+/// Text to put in place of a byte range of a source.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Replacement {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// Apply non-overlapping replacements, which may be given in any order.
+pub fn apply(content: &str, replacements: &[Replacement]) -> String {
+    let mut sorted: Vec<&Replacement> = replacements.iter().collect();
+    sorted.sort_by_key(|replacement| replacement.start);
+    let mut result = String::with_capacity(content.len());
+    let mut pos = 0;
+    for replacement in sorted {
+        result.push_str(&content[pos..replacement.start]);
+        result.push_str(&replacement.text);
+        pos = replacement.end;
+    }
+    result.push_str(&content[pos..]);
+    result
+}
+
+/// `text` as written inside a string literal closed by `quote` whose escape
+/// character is `escape`, or `None` if it can't be, as when it has the quote
+/// and the string is raw.
+pub fn escape_string(text: &str, quote: char, escape: Option<char>) -> Option<String> {
+    let special = |c: char| c == quote || Some(c) == escape;
+    match escape {
+        Some(escape) => Some(text.chars().fold(String::new(), |mut escaped, c| {
+            if special(c) {
+                escaped.push(escape);
+            }
+            escaped.push(c);
+            escaped
+        })),
+        None => (!text.contains(special)).then(|| text.to_owned()),
+    }
+}
+
+/// Lines of unchanged context around each change in a diff.
+const CONTEXT: usize = 3;
+
+/// Lines changed by one or more replacements, `first..=last`.
+struct Change<'a> {
+    first: usize,
+    last: usize,
+    replacements: Vec<&'a Replacement>,
+}
+
+/// Unified diff of applying `replacements` to `content`, as from `a/{path}`
+/// to `b/{path}` so that `git apply` takes it. Empty if there are none.
+pub fn unified_diff(path: &str, content: &str, replacements: &[Replacement]) -> String {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut starts = vec![0];
+    starts.extend(lines.iter().scan(0, |pos, line| {
+        *pos += line.len();
+        Some(*pos)
+    }));
+    let line_of = |offset: usize| starts.partition_point(|&start| start <= offset) - 1;
+
+    let mut sorted: Vec<&Replacement> = replacements.iter().collect();
+    sorted.sort_by_key(|replacement| replacement.start);
+    let mut changes: Vec<Change> = vec![];
+    for replacement in sorted {
+        let first = line_of(replacement.start);
+        let last = line_of(replacement.end.max(replacement.start + 1) - 1);
+        match changes.last_mut() {
+            Some(change) if first <= change.last => {
+                change.last = change.last.max(last);
+                change.replacements.push(replacement);
+            }
+            _ => changes.push(Change {
+                first,
+                last,
+                replacements: vec![replacement],
+            }),
+        }
+    }
+
+    let mut diff = String::new();
+    if changes.is_empty() {
+        return diff;
+    }
+    diff.push_str(&format!("--- a/{path}\n+++ b/{path}\n"));
+    let push_line = |body: &mut String, prefix: char, line: &str| {
+        body.push(prefix);
+        body.push_str(line);
+        if !line.ends_with('\n') {
+            body.push_str("\n\\ No newline at end of file\n");
+        }
+    };
+    // Lines added minus lines removed by earlier hunks.
+    let mut offset = 0isize;
+    let mut changes = changes.into_iter().peekable();
+    while let Some(change) = changes.next() {
+        let old_start = change.first.saturating_sub(CONTEXT);
+        let (mut old_len, mut new_len) = (0, 0);
+        let mut body = String::new();
+        let mut pos = old_start;
+        let mut change = Some(change);
+        while let Some(current) = change {
+            for line in &lines[pos..current.first] {
+                push_line(&mut body, ' ', line);
+            }
+            let context = current.first - pos;
+            let region_start = starts[current.first];
+            let region = &content[region_start..starts[current.last + 1]];
+            let shifted: Vec<Replacement> = current
+                .replacements
+                .iter()
+                .map(|replacement| Replacement {
+                    start: replacement.start - region_start,
+                    end: replacement.end - region_start,
+                    text: replacement.text.clone(),
+                })
+                .collect();
+            let new_region = apply(region, &shifted);
+            for line in &lines[current.first..=current.last] {
+                push_line(&mut body, '-', line);
+            }
+            let added = new_region.split_inclusive('\n').count();
+            for line in new_region.split_inclusive('\n') {
+                push_line(&mut body, '+', line);
+            }
+            old_len += context + current.last + 1 - current.first;
+            new_len += context + added;
+            pos = current.last + 1;
+            change = changes.next_if(|next| next.first - pos <= 2 * CONTEXT);
+        }
+        let end = (pos + CONTEXT).min(lines.len());
+        for line in &lines[pos..end] {
+            push_line(&mut body, ' ', line);
+        }
+        old_len += end - pos;
+        new_len += end - pos;
+        let new_start = old_start as isize + offset;
+        offset += new_len as isize - old_len as isize;
+        let range = |start: isize, len: usize| {
+            // An empty range names the line before it.
+            let start = if len == 0 { start } else { start + 1 };
+            format!("{start},{len}")
+        };
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n{body}",
+            range(old_start as isize, old_len),
+            range(new_start, new_len)
+        ));
+    }
+    diff
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn apply_replacements() {
+        let replacements = [
+            Replacement {
+                start: 12,
+                end: 18,
+                text: "y".to_owned(),
+            },
+            Replacement {
+                start: 3,
+                end: 7,
+                text: "https://s/1".to_owned(),
+            },
+        ];
+        assert_eq!(
+            apply("// long and longer", &replacements),
+            "// https://s/1 and y"
+        );
+    }
+
+    #[test]
+    fn escape() {
+        assert_eq!(
+            escape_string(r#"https://a/\x'""#, '"', Some('\\')).unwrap(),
+            r#"https://a/\\x'\""#
+        );
+        assert_eq!(
+            escape_string(r"https://a/'", '\'', Some('\\')).unwrap(),
+            r"https://a/\'"
+        );
+        assert_eq!(
+            escape_string("https://a/'", '"', None).unwrap(),
+            "https://a/'"
+        );
+        assert!(escape_string("https://a/\"", '"', None).is_none());
+    }
+
+    #[test]
+    fn diff() {
+        let content: String = (1..=20).map(|n| format!("// line {n}\n")).collect();
+        let content = content + "// https://long";
+        let at = |needle: &str, text: &str| {
+            let start = content.find(needle).unwrap();
+            Replacement {
+                start,
+                end: start + needle.len(),
+                text: text.to_owned(),
+            }
+        };
+        let replacements = [
+            at("https://long", "https://s/2"),
+            at("line 2\n", "line 2\n// inserted\n"),
+            at("line 4", "line four"),
+        ];
+        assert_eq!(
+            unified_diff("src/main.c", &content, &replacements),
+            indoc! {r"
+                --- a/src/main.c
+                +++ b/src/main.c
+                @@ -1,7 +1,8 @@
+                 // line 1
+                -// line 2
+                +// line 2
+                +// inserted
+                 // line 3
+                -// line 4
+                +// line four
+                 // line 5
+                 // line 6
+                 // line 7
+                @@ -18,4 +19,4 @@
+                 // line 18
+                 // line 19
+                 // line 20
+                -// https://long
+                \ No newline at end of file
+                +// https://s/2
+                \ No newline at end of file
+            "}
+        );
+        assert_eq!(unified_diff("src/main.c", &content, &[]), "");
+    }
+}
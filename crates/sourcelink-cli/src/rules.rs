@@ -0,0 +1,264 @@
+use crate::normalize::NormalizeConfig;
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use serde::Deserialize;
+use std::fmt;
+use url::Url;
+
+/// What to do with a link.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    #[default]
+    Shorten,
+    Skip,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Shorten => write!(f, "shorten"),
+            Self::Skip => write!(f, "skip"),
+        }
+    }
+}
+
+/// A `[[shorten.rules]]` entry, matching links whose host matches `domain`
+/// and whose path matches `path`, whichever are given.
+#[derive(Deserialize, Debug)]
+#[serde(try_from = "RuleEntry")]
+pub struct Rule {
+    pub domain: Option<GlobMatcher>,
+    pub path: Option<Regex>,
+    pub action: Action,
+}
+
+#[derive(Deserialize)]
+struct RuleEntry {
+    domain: Option<String>,
+    path: Option<String>,
+    action: Action,
+}
+
+impl TryFrom<RuleEntry> for Rule {
+    type Error = anyhow::Error;
+
+    fn try_from(entry: RuleEntry) -> Result<Self, Self::Error> {
+        let domain = match entry.domain {
+            Some(domain) => Some(
+                GlobBuilder::new(&domain)
+                    .case_insensitive(true)
+                    .build()?
+                    .compile_matcher(),
+            ),
+            None => None,
+        };
+        Ok(Self {
+            domain,
+            path: entry.path.as_deref().map(Regex::new).transpose()?,
+            action: entry.action,
+        })
+    }
+}
+
+impl Rule {
+    fn matches(&self, url: &Url) -> bool {
+        let host = url.host_str().unwrap_or_default();
+        self.domain.as_ref().is_none_or(|glob| glob.is_match(host))
+            && self
+                .path
+                .as_ref()
+                .is_none_or(|path| path.is_match(url.path()))
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut conditions = vec![];
+        if let Some(domain) = &self.domain {
+            conditions.push(format!("domain `{}`", domain.glob()));
+        }
+        if let Some(path) = &self.path {
+            conditions.push(format!("path `{path}`"));
+        }
+        if conditions.is_empty() {
+            conditions.push("any link".to_owned());
+        }
+        write!(f, "{}", conditions.join(", "))
+    }
+}
+
+/// Why a link is or isn't shortened.
+#[derive(PartialEq, Debug)]
+pub enum Reason {
+    ShortLink,
+    Invalid,
+    Scheme(String),
+    TooShort(usize),
+    SkipDomain(String),
+    /// Index of the first rule that matched.
+    Rule(usize),
+    Default,
+}
+
+/// Decision on a link along with the reason for it.
+#[derive(PartialEq, Debug)]
+pub struct Verdict {
+    pub action: Action,
+    pub reason: Reason,
+}
+
+impl Verdict {
+    pub fn shorten(&self) -> bool {
+        self.action == Action::Shorten
+    }
+}
+
+/// Rules for which links get shortened, in the order they are checked:
+/// links already pointing at the server, `schemes`, `min_length`,
+/// `skip_domains`, then the first of `rules` that matches, falling back to
+/// `default`.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct ShortenConfig {
+    /// Domains whose links are left alone, subdomains included.
+    pub skip_domains: Vec<String>,
+    /// Schemes of links to shorten.
+    pub schemes: Vec<String>,
+    /// Links shorter than this are left alone.
+    pub min_length: usize,
+    pub rules: Vec<Rule>,
+    /// What to do with links no rule matches.
+    pub default: Action,
+    pub normalize: NormalizeConfig,
+}
+
+impl Default for ShortenConfig {
+    fn default() -> Self {
+        Self {
+            skip_domains: vec![],
+            schemes: vec!["http".to_owned(), "https".to_owned()],
+            min_length: 0,
+            rules: vec![],
+            default: Action::Shorten,
+            normalize: NormalizeConfig::default(),
+        }
+    }
+}
+
+impl ShortenConfig {
+    /// Domain of the `skip_domains` that `url` is on, if any.
+    pub fn skipped_domain(&self, url: &Url) -> Option<&str> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        self.skip_domains
+            .iter()
+            .find(|domain| {
+                let domain = domain.to_ascii_lowercase();
+                host == domain || host.ends_with(&format!(".{domain}"))
+            })
+            .map(String::as_str)
+    }
+
+    /// Whether to shorten a link that isn't a short link already, and why.
+    pub fn verdict(&self, url: &str) -> Verdict {
+        let skip = |reason| Verdict {
+            action: Action::Skip,
+            reason,
+        };
+        let Ok(parsed) = Url::parse(url) else {
+            return skip(Reason::Invalid);
+        };
+        if !self
+            .schemes
+            .iter()
+            .any(|scheme| scheme.eq_ignore_ascii_case(parsed.scheme()))
+        {
+            return skip(Reason::Scheme(parsed.scheme().to_owned()));
+        }
+        if url.len() < self.min_length {
+            return skip(Reason::TooShort(self.min_length));
+        }
+        if let Some(domain) = self.skipped_domain(&parsed) {
+            return skip(Reason::SkipDomain(domain.to_owned()));
+        }
+        match self.rules.iter().position(|rule| rule.matches(&parsed)) {
+            Some(i) => Verdict {
+                action: self.rules[i].action,
+                reason: Reason::Rule(i),
+            },
+            None => Verdict {
+                action: self.default,
+                reason: Reason::Default,
+            },
+        }
+    }
+
+    /// Describe a verdict for `explain`.
+    pub fn describe(&self, verdict: &Verdict) -> String {
+        match &verdict.reason {
+            Reason::ShortLink => "already a short link".to_owned(),
+            Reason::Invalid => "not a valid URL".to_owned(),
+            Reason::Scheme(scheme) => format!("scheme `{scheme}` is not in `schemes`"),
+            Reason::TooShort(min) => format!("shorter than `min_length` {min}"),
+            Reason::SkipDomain(domain) => format!("`{domain}` is in `skip_domains`"),
+            Reason::Rule(i) => format!("rule {} ({})", i + 1, self.rules[*i]),
+            Reason::Default => "no rule matched, using `default`".to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn verdicts() {
+        let config: ShortenConfig = toml::from_str(indoc! {r#"
+            skip_domains = ["spdx.org"]
+            schemes = ["https"]
+            min_length = 20
+            default = "skip"
+
+            [[rules]]
+            domain = "*.internal.example.com"
+            action = "skip"
+
+            [[rules]]
+            domain = "*example.com"
+            path = "^/docs/"
+            action = "shorten"
+        "#})
+        .unwrap();
+        let reason = |url| config.verdict(url).reason;
+        assert_eq!(
+            reason("http://example.com/docs/a"),
+            Reason::Scheme("http".to_owned())
+        );
+        assert_eq!(reason("https://x.io/"), Reason::TooShort(20));
+        assert_eq!(
+            reason("https://spdx.org/licenses/MIT"),
+            Reason::SkipDomain("spdx.org".to_owned())
+        );
+        assert_eq!(
+            reason("https://wiki.internal.example.com/docs/a"),
+            Reason::Rule(0)
+        );
+        let verdict = config.verdict("https://www.EXAMPLE.com/docs/a");
+        assert!(verdict.shorten());
+        assert_eq!(verdict.reason, Reason::Rule(1));
+        assert_eq!(
+            config.describe(&verdict),
+            "rule 2 (domain `*example.com`, path `^/docs/`)"
+        );
+        let verdict = config.verdict("https://example.com/blog/a");
+        assert!(!verdict.shorten());
+        assert_eq!(verdict.reason, Reason::Default);
+
+        let config = ShortenConfig::default();
+        assert!(config.verdict("http://a.b/").shorten());
+        assert!(
+            toml::from_str::<ShortenConfig>("[[rules]]\npath = \"(\"\naction = \"skip\"").is_err()
+        );
+    }
+}
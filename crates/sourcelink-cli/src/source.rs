@@ -0,0 +1,197 @@
+use crate::{config::Config, error::SourcelinkError};
+use anyhow::{Context, Result};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use lazy_static::lazy_static;
+use regex::bytes::Regex;
+use std::{fmt, fs, path::Path};
+
+lazy_static! {
+    /// PEP 263 declaration, which also matches Emacs' `-*- coding: x -*-`.
+    static ref CODING_COOKIE: Regex =
+        Regex::new(r"^[ \t\f]*#.*?coding[:=][ \t]*([-\w.]+)").unwrap();
+}
+
+/// Where a file's encoding came from.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EncodingSource {
+    Bom,
+    Override,
+    Cookie,
+    Default,
+}
+
+impl fmt::Display for EncodingSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bom => write!(f, "byte order mark"),
+            Self::Override => write!(f, "config override"),
+            Self::Cookie => write!(f, "coding declaration"),
+            Self::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// A source file decoded to UTF-8, remembering how to write it back.
+#[derive(Debug)]
+pub struct SourceFile {
+    pub text: String,
+    pub encoding: &'static Encoding,
+    pub source: EncodingSource,
+    /// Whether the file starts with a byte order mark.
+    pub bom: bool,
+    /// Whether encoding `text` gives back the original bytes. Some legacy
+    /// encodings map several byte sequences to the same character.
+    lossless: bool,
+}
+
+/// Look up an encoding by label, also accepting Python's spellings such as
+/// `latin-1`, which WHATWG labels lack.
+pub fn encoding_for_label(label: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(label.as_bytes()).or_else(|| {
+        let label: String = label.chars().filter(|c| !matches!(c, '-' | '_')).collect();
+        Encoding::for_label(label.as_bytes())
+    })
+}
+
+/// Encoding named by a coding declaration on one of the first two lines.
+fn coding_cookie(bytes: &[u8]) -> Option<&'static Encoding> {
+    bytes
+        .split(|&byte| byte == b'\n')
+        .take(2)
+        .find_map(|line| CODING_COOKIE.captures(line))
+        .and_then(|captures| encoding_for_label(&String::from_utf8_lossy(&captures[1])))
+}
+
+impl SourceFile {
+    /// Decode a file using its BOM, then a config override, then a coding
+    /// declaration, falling back to UTF-8.
+    pub fn decode(bytes: &[u8], filename: &str, config: &Config) -> Result<Self, SourcelinkError> {
+        let (encoding, source, bom_len) = match Encoding::for_bom(bytes) {
+            Some((encoding, len)) => (encoding, EncodingSource::Bom, len),
+            None => match config.encoding(filename) {
+                Some(encoding) => (encoding, EncodingSource::Override, 0),
+                None => match coding_cookie(bytes) {
+                    Some(encoding) => (encoding, EncodingSource::Cookie, 0),
+                    None => (UTF_8, EncodingSource::Default, 0),
+                },
+            },
+        };
+        let text = encoding
+            .decode_without_bom_handling_and_without_replacement(&bytes[bom_len..])
+            .ok_or_else(|| SourcelinkError::Malformed(encoding.name().to_owned()))?
+            .into_owned();
+        let mut file = Self {
+            text,
+            encoding,
+            source,
+            bom: bom_len > 0,
+            lossless: true,
+        };
+        if encoding != UTF_8 {
+            file.lossless = file
+                .encode(&file.text)
+                .is_ok_and(|encoded| encoded == bytes);
+        }
+        Ok(file)
+    }
+
+    /// Encode `text` the way the file was encoded, including its BOM.
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, SourcelinkError> {
+        let mut bytes = vec![];
+        // encoding_rs only decodes UTF-16, so encode it by hand.
+        if self.encoding == UTF_16LE {
+            if self.bom {
+                bytes.extend_from_slice(b"\xFF\xFE");
+            }
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        } else if self.encoding == UTF_16BE {
+            if self.bom {
+                bytes.extend_from_slice(b"\xFE\xFF");
+            }
+            bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+        } else {
+            if self.bom {
+                bytes.extend_from_slice(b"\xEF\xBB\xBF");
+            }
+            let (encoded, _, unmappable) = self.encoding.encode(text);
+            if unmappable {
+                return Err(SourcelinkError::Unencodable(
+                    self.encoding.name().to_owned(),
+                ));
+            }
+            bytes.extend_from_slice(&encoded);
+        }
+        Ok(bytes)
+    }
+
+    /// Encode `text` as a replacement for the file's contents, refusing to if
+    /// unchanged parts of the file would not come out the same.
+    pub fn to_bytes(&self, text: &str) -> Result<Vec<u8>, SourcelinkError> {
+        if !self.lossless {
+            return Err(SourcelinkError::LossyEncoding(
+                self.encoding.name().to_owned(),
+            ));
+        }
+        self.encode(text)
+    }
+
+    /// Write `text` back to `path` in the file's original encoding.
+    pub fn write(&self, path: &Path, text: &str) -> Result<()> {
+        fs::write(path, self.to_bytes(text)?)
+            .with_context(|| format!("Unable to write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use encoding_rs::WINDOWS_1252;
+
+    #[test]
+    fn decode() {
+        let config =
+            Config::parse("[[encodings]]\npattern = \"*.c\"\nencoding = \"latin1\"").unwrap();
+        let latin1 = b"/* caf\xE9 https://example.com */\n";
+        let file = SourceFile::decode(latin1, "main.c", &config).unwrap();
+        assert_eq!(file.text, "/* café https://example.com */\n");
+        assert_eq!(file.encoding, WINDOWS_1252);
+        assert_eq!(file.encode(&file.text).unwrap(), latin1);
+        assert!(SourceFile::decode(latin1, "main.h", &config).is_err());
+
+        let python = b"# -*- coding: latin-1 -*-\n# \xA9 2024\n";
+        let file = SourceFile::decode(python, "setup.py", &config).unwrap();
+        assert_eq!(file.source, EncodingSource::Cookie);
+        assert_eq!(file.text, "# -*- coding: latin-1 -*-\n# © 2024\n");
+    }
+
+    #[test]
+    fn round_trip_bom() {
+        let config = Config::default();
+        let utf16: Vec<u8> = b"\xFF\xFE"
+            .iter()
+            .copied()
+            .chain(
+                "// https://example.com\r\n"
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes),
+            )
+            .collect();
+        let file = SourceFile::decode(&utf16, "main.c", &config).unwrap();
+        assert!(file.bom);
+        assert_eq!(file.text, "// https://example.com\r\n");
+        assert_eq!(file.encode(&file.text).unwrap(), utf16);
+
+        let utf8 = b"\xEF\xBB\xBF// x\n";
+        let file = SourceFile::decode(utf8, "main.c", &config).unwrap();
+        assert_eq!(file.text, "// x\n");
+        assert_eq!(file.encode("// y\n").unwrap(), b"\xEF\xBB\xBF// y\n");
+
+        let file = SourceFile::decode(b"// x\n", "main.c", &config).unwrap();
+        assert_eq!(file.encoding, UTF_8);
+        let latin1 = SourceFile {
+            encoding: WINDOWS_1252,
+            ..file
+        };
+        assert!(latin1.encode("// \u{1F600}").is_err());
+    }
+}
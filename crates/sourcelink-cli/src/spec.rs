@@ -0,0 +1,303 @@
+use serde::Deserialize;
+
+/// Declarative description of a language's comment and string syntax.
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct LanguageSpec {
+    /// Name used to refer to the language in output.
+    pub name: String,
+    /// Other names the language goes by, e.g. in editor modelines.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// File extensions, without the leading dot.
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// Exact file names, for files without a telling extension.
+    #[serde(default)]
+    pub filenames: Vec<String>,
+    /// Interpreters named in a `#!` line, ignoring version suffixes.
+    #[serde(default)]
+    pub interpreters: Vec<String>,
+    /// Markers that start a comment running to the end of the line.
+    #[serde(default)]
+    pub line_comments: Vec<String>,
+    #[serde(default)]
+    pub block_comments: Vec<BlockComment>,
+    #[serde(default)]
+    pub strings: Vec<StringDelimiter>,
+}
+
+/// Delimiters of a block comment, e.g. `/*` and `*/`.
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct BlockComment {
+    pub open: String,
+    pub close: String,
+    /// Whether block comments of this kind may contain each other.
+    #[serde(default)]
+    pub nested: bool,
+    /// Character that may be repeated before the last character of `open`,
+    /// as many times as it then has to follow the first character of `close`.
+    /// Lua comments are `--[[`, `--[=[` and so on with `=` as the fence.
+    #[serde(default)]
+    pub fence: Option<char>,
+}
+
+/// Delimiters of a string literal, within which comment markers are ignored.
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct StringDelimiter {
+    pub open: String,
+    pub close: String,
+    /// Character escaping the one following it.
+    #[serde(default = "default_escape")]
+    pub escape: Option<char>,
+    /// Raw strings do not process escapes.
+    #[serde(default)]
+    pub raw: bool,
+    /// Whether the string may span lines. Otherwise a line break ends it and
+    /// is reported as a lexer error.
+    #[serde(default)]
+    pub multiline: bool,
+    /// Character that may be repeated before the last character of `open`,
+    /// as many times as it then has to follow the first character of `close`.
+    /// Rust raw strings are `r"`, `r#"`, `r##"` and so on with `#` as the
+    /// fence.
+    #[serde(default)]
+    pub fence: Option<char>,
+    /// Whether `open` followed by an identifier, without `close` right after
+    /// it, starts a lifetime or label rather than a string, as `'` does in
+    /// Rust.
+    #[serde(default)]
+    pub lifetimes: bool,
+    /// Whether this is a regular expression literal, only opened where a
+    /// value is expected, so that `/` in `a / b` still divides.
+    #[serde(default)]
+    pub regex: bool,
+}
+
+fn default_escape() -> Option<char> {
+    Some('\\')
+}
+
+/// Length of `open` at the start of `text`, with `fence` repeated before its
+/// last character, and the number of fence characters.
+fn fenced_opener(open: &str, fence: Option<char>, text: &str) -> Option<(usize, usize)> {
+    let Some(fence) = fence else {
+        return text.starts_with(open).then_some((open.len(), 0));
+    };
+    let last = open.chars().last()?;
+    let rest = text.strip_prefix(&open[..open.len() - last.len_utf8()])?;
+    let fences = rest.chars().take_while(|&c| c == fence).count();
+    rest[fences * fence.len_utf8()..]
+        .starts_with(last)
+        .then_some((open.len() + fences * fence.len_utf8(), fences))
+}
+
+/// Length of `close` at the start of `rest`, with `fences` fence characters
+/// after its first character.
+fn fenced_closer(close: &str, fence: Option<char>, rest: &str, fences: usize) -> Option<usize> {
+    let Some(fence) = fence else {
+        return rest.starts_with(close).then_some(close.len());
+    };
+    let first = close.chars().next()?;
+    let after = rest.strip_prefix(first)?;
+    let fenced: String = std::iter::repeat_n(fence, fences).collect();
+    after
+        .strip_prefix(fenced.as_str())?
+        .starts_with(&close[first.len_utf8()..])
+        .then_some(close.len() + fenced.len())
+}
+
+impl LanguageSpec {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            aliases: vec![],
+            extensions: vec![],
+            filenames: vec![],
+            interpreters: vec![],
+            line_comments: vec![],
+            block_comments: vec![],
+            strings: vec![],
+        }
+    }
+
+    pub fn with_aliases(mut self, aliases: &[&str]) -> Self {
+        self.aliases
+            .extend(aliases.iter().map(|alias| alias.to_string()));
+        self
+    }
+
+    pub fn with_extensions(mut self, extensions: &[&str]) -> Self {
+        self.extensions
+            .extend(extensions.iter().map(|ext| ext.to_string()));
+        self
+    }
+
+    pub fn with_filenames(mut self, filenames: &[&str]) -> Self {
+        self.filenames
+            .extend(filenames.iter().map(|filename| filename.to_string()));
+        self
+    }
+
+    pub fn with_interpreters(mut self, interpreters: &[&str]) -> Self {
+        self.interpreters.extend(
+            interpreters
+                .iter()
+                .map(|interpreter| interpreter.to_string()),
+        );
+        self
+    }
+
+    pub fn with_line_comment(mut self, marker: &str) -> Self {
+        self.line_comments.push(marker.to_owned());
+        self
+    }
+
+    pub fn with_block_comment(mut self, open: &str, close: &str, nested: bool) -> Self {
+        self.block_comments.push(BlockComment {
+            open: open.to_owned(),
+            close: close.to_owned(),
+            nested,
+            fence: None,
+        });
+        self
+    }
+
+    pub fn with_fenced_block_comment(mut self, open: &str, close: &str, fence: char) -> Self {
+        self = self.with_block_comment(open, close, false);
+        self.block_comments.last_mut().unwrap().fence = Some(fence);
+        self
+    }
+
+    pub fn with_string(mut self, open: &str, close: &str, multiline: bool) -> Self {
+        self.strings.push(StringDelimiter {
+            open: open.to_owned(),
+            close: close.to_owned(),
+            escape: default_escape(),
+            raw: false,
+            multiline,
+            fence: None,
+            lifetimes: false,
+            regex: false,
+        });
+        self
+    }
+
+    /// Add a regular expression literal delimiter, as in JavaScript.
+    pub fn with_regex_literal(mut self, delimiter: &str) -> Self {
+        self = self.with_string(delimiter, delimiter, false);
+        self.strings.last_mut().unwrap().regex = true;
+        self
+    }
+
+    /// Add a character literal delimiter that also starts lifetimes.
+    pub fn with_char_literal(mut self, quote: &str) -> Self {
+        self.strings.push(StringDelimiter {
+            open: quote.to_owned(),
+            close: quote.to_owned(),
+            escape: default_escape(),
+            raw: false,
+            multiline: false,
+            fence: None,
+            lifetimes: true,
+            regex: false,
+        });
+        self
+    }
+
+    pub fn with_raw_string(mut self, open: &str, close: &str, multiline: bool) -> Self {
+        self.strings.push(StringDelimiter {
+            open: open.to_owned(),
+            close: close.to_owned(),
+            escape: None,
+            raw: true,
+            multiline,
+            fence: None,
+            lifetimes: false,
+            regex: false,
+        });
+        self
+    }
+
+    pub fn with_fenced_raw_string(mut self, open: &str, close: &str, fence: char) -> Self {
+        self = self.with_raw_string(open, close, true);
+        self.strings.last_mut().unwrap().fence = Some(fence);
+        self
+    }
+
+    pub fn matches_name(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+            || self
+                .aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(name))
+    }
+
+    pub fn matches_extension(&self, ext: &str) -> bool {
+        self.extensions.iter().any(|e| e == ext)
+    }
+
+    pub fn matches_filename(&self, filename: &str) -> bool {
+        self.filenames.iter().any(|f| f == filename)
+    }
+
+    pub fn matches_interpreter(&self, interpreter: &str) -> bool {
+        self.interpreters.iter().any(|i| i == interpreter)
+    }
+}
+
+impl BlockComment {
+    /// Length of the opener at the start of `text` and the number of fence
+    /// characters in it, or `None` if no comment is opened there.
+    pub fn opener(&self, text: &str) -> Option<(usize, usize)> {
+        fenced_opener(&self.open, self.fence, text)
+    }
+
+    /// Length of the closer at the start of `rest` for an opener with
+    /// `fences` fence characters, or `None` if the comment isn't closed there.
+    pub fn closer(&self, rest: &str, fences: usize) -> Option<usize> {
+        fenced_closer(&self.close, self.fence, rest, fences)
+    }
+}
+
+impl StringDelimiter {
+    /// First character of `close`, which ends the string unless escaped.
+    pub fn quote(&self) -> char {
+        self.close.chars().next().unwrap_or_default()
+    }
+
+    pub fn escape(&self) -> Option<char> {
+        if self.raw {
+            None
+        } else {
+            self.escape
+        }
+    }
+
+    /// Length of the closer for an opener with `fences` fence characters.
+    pub fn close_len(&self, fences: usize) -> usize {
+        self.close.len() + fences * self.fence.map_or(0, char::len_utf8)
+    }
+
+    /// Length of the opener at the start of `text` and the number of fence
+    /// characters in it, or `None` if the string isn't opened there.
+    pub fn opener(&self, text: &str) -> Option<(usize, usize)> {
+        let (len, fences) = fenced_opener(&self.open, self.fence, text)?;
+        if self.lifetimes {
+            let body = &text[len..];
+            let ident = body.len()
+                - body
+                    .trim_start_matches(|c: char| c == '_' || c.is_alphanumeric())
+                    .len();
+            if ident > 0 && !body[ident..].starts_with(&self.close) {
+                return None;
+            }
+        }
+        Some((len, fences))
+    }
+
+    /// Length of the closer at the start of `rest` for an opener with
+    /// `fences` fence characters, or `None` if the string isn't closed there.
+    pub fn closer(&self, rest: &str, fences: usize) -> Option<usize> {
+        fenced_closer(&self.close, self.fence, rest, fences)
+    }
+}
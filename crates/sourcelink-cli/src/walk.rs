@@ -0,0 +1,100 @@
+use crate::{config::Config, lang::Lang};
+use anyhow::Result;
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// Bytes read from each end of a file to detect its language from a `#!`
+/// line, modeline or heuristics when the path doesn't tell.
+const SAMPLE_LEN: u64 = 4096;
+
+/// Collect the files under `paths`, skipping hidden entries inside directories
+/// and files whose language can't be detected. Files named explicitly are
+/// always included. The result is sorted so output is deterministic.
+pub fn collect_files(paths: &[PathBuf], config: &Config) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for path in paths {
+        if path.is_dir() {
+            walk_dir(path, config, &mut files)?;
+        } else {
+            files.push(path.clone());
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+fn walk_dir(dir: &Path, config: &Config, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            walk_dir(&path, config, files)?;
+        } else if file_type.is_file() && scannable(&path, config) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Whether the language of a file can be detected, from its path or else from
+/// the start and end of it. Files that can't be read are kept so that
+/// scanning them reports the error.
+fn scannable(path: &Path, config: &Config) -> bool {
+    let filename = path.to_string_lossy();
+    if Lang::detect_path(&filename, config).is_some() {
+        return true;
+    }
+    sample(path).is_none_or(|sample| Lang::detect(&filename, &sample, config).is_ok())
+}
+
+/// The first and last `SAMPLE_LEN` bytes of a file, or all of it if it is
+/// short.
+fn sample(path: &Path) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    let mut bytes = vec![];
+    (&mut file).take(SAMPLE_LEN).read_to_end(&mut bytes).ok()?;
+    if len > 2 * SAMPLE_LEN {
+        bytes.push(b'\n');
+        file.seek(SeekFrom::End(-(SAMPLE_LEN as i64))).ok()?;
+    }
+    file.read_to_end(&mut bytes).ok()?;
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn skip_undetected() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &[u8]| fs::write(dir.path().join(name), content).unwrap();
+        write("main.c", b"int x;\n");
+        write("run", b"#!/usr/bin/env python3\nprint()\n");
+        write("notes.txt", b"See above.\n# vim: set ft=python:\n");
+        write("image.png", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+        write("data.csv", b"a,b\n1,2\n");
+        let mut long = b"# -*- mode: lua -*-\n".to_vec();
+        long.resize(3 * SAMPLE_LEN as usize, b'-');
+        write("long.txt", &long);
+        let config = Config::default();
+        let files = collect_files(&[dir.path().to_owned()], &config).unwrap();
+        let names: Vec<_> = files
+            .iter()
+            .map(|file| file.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["long.txt", "main.c", "notes.txt", "run"]);
+
+        let explicit = [dir.path().join("data.csv")];
+        assert_eq!(collect_files(&explicit, &config).unwrap(), explicit);
+    }
+}
@@ -10,6 +10,8 @@ pub enum SourcelinkError {
     SQLXError(#[from] sqlx::Error),
     #[error("Unauthorized")]
     Unauthorized,
+    #[error("Invalid link id {0}")]
+    InvalidId(String),
 }
 
 impl SourcelinkError {
@@ -17,6 +19,7 @@ impl SourcelinkError {
         match self {
             Self::SQLXError(_) => "SQLXError",
             Self::Unauthorized => "Unauthorized",
+            Self::InvalidId(_) => "InvalidId",
         }
         .to_owned()
     }
@@ -32,9 +35,11 @@ impl IntoResponse for SourcelinkError {
         match self {
             Self::SQLXError(err) => match err {
                 sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
+                sqlx::Error::Database(err) if err.is_unique_violation() => StatusCode::CONFLICT,
                 _ => StatusCode::INTERNAL_SERVER_ERROR,
             },
             Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::InvalidId(_) => StatusCode::BAD_REQUEST,
         }
         .into_response()
     }
@@ -215,7 +215,19 @@ async fn create_link(
 
     authenticate(&headers, &ctx)?;
 
-    let id = nanoid!(6);
+    let id = match params.id {
+        Some(id) => {
+            let valid = !id.is_empty()
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if !valid {
+                return Err(SourcelinkError::InvalidId(id));
+            }
+            id
+        }
+        None => nanoid!(6),
+    };
     let link = sqlx::query_as!(
         LinkRow,
         r#"INSERT INTO links(id, url, project_id) VALUES (?, ?, ?) RETURNING id, url, project_id"#,
@@ -306,3 +318,58 @@ async fn redirect_link(Path(id): Path<String>, ctx: State<Arc<Context>>) -> Resu
 
     Ok(Redirect::permanent(&link.url))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::response::IntoResponse;
+
+    fn context(pool: SqlitePool) -> Arc<Context> {
+        Arc::new(Context {
+            config: Config {
+                database_url: String::new(),
+                host: String::new(),
+                api_key: "key".to_owned(),
+            },
+            pool,
+        })
+    }
+
+    async fn create(ctx: &Arc<Context>, id: Option<&str>) -> Result<(StatusCode, Json<LinkRow>)> {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-API-Key", "key".parse().unwrap());
+        let params = CreateLink {
+            project_id: "p".to_owned(),
+            url: "https://example.com/spec".to_owned(),
+            id: id.map(str::to_owned),
+        };
+        create_link(headers, State(ctx.clone()), Json(params)).await
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn duplicate_ids(pool: SqlitePool) {
+        let ctx = context(pool);
+        let (status, Json(link)) = create(&ctx, Some("spec")).await.unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(link.id, "spec");
+        let (_, Json(link)) = create(&ctx, None).await.unwrap();
+        assert_eq!(link.id.len(), 6);
+        let err = create(&ctx, Some("spec")).await.unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::CONFLICT);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn invalid_ids(pool: SqlitePool) {
+        let ctx = context(pool);
+        for id in ["", "has space", "a/b", "ünï"] {
+            let err = create(&ctx, Some(id)).await.unwrap_err();
+            assert!(matches!(&err, SourcelinkError::InvalidId(invalid) if invalid == id));
+            assert_eq!(err.into_response().status(), StatusCode::BAD_REQUEST);
+        }
+        let count = sqlx::query_scalar!(r#"SELECT COUNT(*) FROM links"#)
+            .fetch_one(&ctx.pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+}
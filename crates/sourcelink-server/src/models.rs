@@ -64,4 +64,7 @@ pub struct CreateProject {
 pub struct CreateLink {
     pub project_id: String,
     pub url: String,
+    /// Id to give the link instead of a random one.
+    #[serde(default)]
+    pub id: Option<String>,
 }
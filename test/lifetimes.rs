@@ -0,0 +1,11 @@
+// https://github.com/cschram/sourcelink
+fn first<'a>(x: &'a str, _y: &'a str) -> &'a str { // https://example.com/lifetimes
+    let _quote = '\'';
+    let _x = 'x'; // https://example.com/char
+    'outer: loop {
+        break 'outer;
+    }
+    let _raw = r##"// https://example.com/not-a-comment "# still raw"##;
+    let _bytes = br###"/* https://example.com/not-a-comment */"###;
+    x
+} // https://example.com/end